@@ -0,0 +1,220 @@
+//! An async flavour of the driver, for executors that cannot afford to block for conversions
+//! and EEPROM writes.
+//!
+//! Individual 1-wire slots are timing-critical, so bus traffic still happens synchronously (using
+//! the blocking delay). Only the long waits (conversions, EEPROM copy and recall) yield to the executor,
+//! so the delay has to implement both the blocking and the async `DelayNs` traits.
+
+use crate::target::Target;
+use crate::{
+    commands, decode_scratchpad, read_scratchpad, send_command, Address, BusyPoll, Ds18b20Result,
+    OneWireMaster, PowerMode, Resolution, SensorData, SensorFamily, StrongPullup,
+    BUSY_POLL_INTERVAL_MILLIS, EEPROM_WRITE_MILLIS, RECALL_MILLIS,
+};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::delay::DelayNs;
+use embedded_hal_async::delay::DelayNs as AsyncDelayNs;
+
+pub struct Ds18b20 {
+    inner: crate::Ds18b20,
+}
+
+impl Ds18b20 {
+    /// Checks that the given address contains the correct family code, then returns a device
+    pub fn new<E>(address: Address) -> Ds18b20Result<Ds18b20, E> {
+        Ok(Ds18b20 {
+            inner: crate::Ds18b20::new(address)?,
+        })
+    }
+
+    /// A device that is the only one on its bus, which is addressed with Skip ROM
+    pub fn single_on_bus() -> Ds18b20 {
+        Ds18b20 {
+            inner: crate::Ds18b20::single_on_bus(),
+        }
+    }
+
+    pub fn family(&self) -> SensorFamily {
+        self.inner.family()
+    }
+
+    /// Returns the resolution that conversions are timed for
+    pub fn resolution(&self) -> Resolution {
+        self.inner.resolution()
+    }
+
+    /// Sets the resolution that conversions are timed for, without changing the device configuration
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.inner.set_resolution(resolution)
+    }
+
+    /// Asks the device how it is powered, and remembers the result for later operations
+    pub async fn detect_power_mode<B, E>(
+        &mut self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<PowerMode, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.inner.target.detect_power_mode(onewire, delay)
+    }
+
+    /// Starts a temperature measurement, waits for it to finish and reads the result.
+    ///
+    /// Externally powered devices are polled, so this returns as soon as the conversion is done.
+    /// In parasite power mode the strong pull-up is held for the maximum measurement time.
+    /// Failed steps are retried as the retry policy says, and `last_retries` counts the retries of all steps.
+    pub async fn measure<B, E, D>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut D,
+    ) -> Ds18b20Result<SensorData, E>
+    where
+        B: OneWireMaster<Error = E>,
+        D: DelayNs + AsyncDelayNs,
+    {
+        let target = &self.inner.target;
+        let millis = self.resolution().max_measurement_time_millis();
+        let hold = target.begin_conversion(onewire, pullup, delay)?;
+        match self.power_mode() {
+            PowerMode::Parasite => AsyncDelayNs::delay_ms(delay, u32::from(millis)).await,
+            PowerMode::External => wait_for_high_read_slot(target, onewire, delay, millis).await?,
+        }
+        hold.release(onewire, pullup, delay)?;
+        let scratchpad = target
+            .retry_policy
+            .resume(&target.last_retries, delay, |delay| {
+                read_scratchpad(target.address(), onewire, delay)
+            })?;
+        decode_scratchpad(&scratchpad, self.family())
+    }
+
+    pub async fn read_data<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<SensorData, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.inner.read_data(onewire, delay)
+    }
+
+    pub async fn set_config<B, E>(
+        &mut self,
+        alarm_temp_low: i8,
+        alarm_temp_high: i8,
+        resolution: Resolution,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.inner
+            .set_config(alarm_temp_low, alarm_temp_high, resolution, onewire, delay)
+    }
+
+    /// Copies the alarm thresholds and resolution to EEPROM, then recalls them to check that they were written.
+    /// Only the copy yields, the recall takes at most 10ms
+    pub async fn save_to_eeprom<B, E, D>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut D,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+        D: DelayNs + AsyncDelayNs,
+    {
+        let target = &self.inner.target;
+        let (saved, hold) = target.begin_eeprom_copy(onewire, pullup, delay)?;
+        AsyncDelayNs::delay_ms(delay, u32::from(EEPROM_WRITE_MILLIS)).await;
+        target.end_eeprom_copy(&saved, hold, onewire, pullup, delay)
+    }
+
+    pub async fn recall_from_eeprom<B, E, D>(
+        &self,
+        onewire: &mut B,
+        delay: &mut D,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+        D: DelayNs + AsyncDelayNs,
+    {
+        let target = &self.inner.target;
+        target.retry(delay, |delay| {
+            send_command(commands::RECALL_EEPROM, target.address(), onewire, delay)
+        })?;
+        wait_for_high_read_slot(target, onewire, delay, RECALL_MILLIS).await
+    }
+
+    /// Returns true if the device is powered from the data line
+    pub async fn is_parasite_powered<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<bool, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.inner.target.is_parasite_powered(onewire, delay)
+    }
+}
+
+impl Deref for Ds18b20 {
+    type Target = Target;
+
+    fn deref(&self) -> &Target {
+        &self.inner.target
+    }
+}
+
+impl DerefMut for Ds18b20 {
+    fn deref_mut(&mut self) -> &mut Target {
+        &mut self.inner.target
+    }
+}
+
+impl From<crate::Ds18b20> for Ds18b20 {
+    fn from(inner: crate::Ds18b20) -> Ds18b20 {
+        Ds18b20 { inner }
+    }
+}
+
+/// Like the blocking version, but yields between read slots. A timeout is retried as the retry policy
+/// says, adding to the retries of the command that started the wait
+async fn wait_for_high_read_slot<B, E, D>(
+    target: &Target,
+    onewire: &mut B,
+    delay: &mut D,
+    timeout_millis: u16,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+    D: DelayNs + AsyncDelayNs,
+{
+    let mut attempts = 1;
+    loop {
+        let mut poll = BusyPoll::new(timeout_millis);
+        let result = loop {
+            match poll.is_done(onewire, delay) {
+                Ok(false) => AsyncDelayNs::delay_ms(delay, BUSY_POLL_INTERVAL_MILLIS).await,
+                Ok(true) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        match result {
+            Err(err)
+                if target
+                    .retry_policy
+                    .should_retry(&mut attempts, &target.last_retries, &err) =>
+            {
+                AsyncDelayNs::delay_ms(delay, target.retry_policy.backoff_millis).await
+            }
+            result => return result,
+        }
+    }
+}
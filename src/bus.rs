@@ -0,0 +1,307 @@
+//! The 1-wire operations the driver needs from a bus master, so it can run on any transport
+//! (a bit-banged pin, an I²C or UART bridge, ...).
+
+use embedded_hal::delay::DelayNs;
+use one_wire_bus::commands;
+use one_wire_bus::crc::check_crc8;
+use one_wire_bus::{Address, OneWireError, OneWireResult};
+
+/// A 1-wire bus master. Only `reset`, `read_bit` and `write_bit` are required, everything else
+/// is built on top of them, but can be overridden when the transport has faster ways of doing it.
+///
+/// The delay is passed to every operation for masters that time the slots themselves, and can be
+/// ignored by masters that have hardware timing.
+pub trait OneWireMaster {
+    type Error;
+
+    /// Sends a reset pulse, then returns true if a device is present
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error>;
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error>;
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error>;
+
+    fn read_byte(&mut self, delay: &mut impl DelayNs) -> OneWireResult<u8, Self::Error> {
+        let mut output: u8 = 0;
+        for _ in 0..8 {
+            output >>= 1;
+            if self.read_bit(delay)? {
+                output |= 0x80;
+            }
+        }
+        Ok(output)
+    }
+
+    fn read_bytes(
+        &mut self,
+        output: &mut [u8],
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        for byte in output.iter_mut() {
+            *byte = self.read_byte(delay)?;
+        }
+        Ok(())
+    }
+
+    fn write_byte(
+        &mut self,
+        mut value: u8,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        for _ in 0..8 {
+            self.write_bit(value & 0x01 == 0x01, delay)?;
+            value >>= 1;
+        }
+        Ok(())
+    }
+
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        for byte in bytes {
+            self.write_byte(*byte, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Address a specific device. All others will wait for a reset pulse.
+    /// This should only be called after a reset, and should be immediately followed by another command
+    fn match_address(
+        &mut self,
+        address: &Address,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        self.write_byte(commands::MATCH_ROM, delay)?;
+        self.write_bytes(&address.0.to_le_bytes(), delay)?;
+        Ok(())
+    }
+
+    /// Address all devices on the bus simultaneously.
+    /// This should only be called after a reset, and should be immediately followed by another command
+    fn skip_address(&mut self, delay: &mut impl DelayNs) -> OneWireResult<(), Self::Error> {
+        self.write_byte(commands::SKIP_ROM, delay)?;
+        Ok(())
+    }
+
+    /// Sends a reset, followed with either a SKIP_ROM or MATCH_ROM (with an address), and then the supplied command
+    /// This should be followed by any reading/writing, if needed by the command used
+    fn send_command(
+        &mut self,
+        command: u8,
+        address: Option<&Address>,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        self.reset(delay)?;
+        if let Some(address) = address {
+            self.match_address(address, delay)?;
+        } else {
+            self.skip_address(delay)?;
+        }
+        self.write_byte(command, delay)?;
+        Ok(())
+    }
+
+    /// Arms the master's own strong pull-up (if it has one), to take over the bus right after the
+    /// next byte is written. Returns false if there is none, in which case the `StrongPullup` passed
+    /// to the driver is used instead.
+    fn arm_strong_pullup(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Ends a strong pull-up started with `arm_strong_pullup`
+    fn disarm_strong_pullup(
+        &mut self,
+        _delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        Ok(())
+    }
+
+    /// One step of a ROM search: reads an address bit and its complement, then writes the bit to follow.
+    /// If the devices disagree, `direction` is written. Returns the bit, its complement and the direction taken.
+    fn search_triplet(
+        &mut self,
+        direction: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(bool, bool, bool), Self::Error> {
+        let id_bit = self.read_bit(delay)?;
+        let complement_bit = self.read_bit(delay)?;
+        let taken = match (id_bit, complement_bit) {
+            (false, false) => direction,
+            (id_bit, _) => id_bit,
+        };
+        self.write_bit(taken, delay)?;
+        Ok((id_bit, complement_bit, taken))
+    }
+
+    /// Returns an iterator that iterates over all device addresses on the bus
+    /// They can be filtered to only alarming devices if needed
+    /// There is no requirement to immediately finish iterating all devices, but if devices are
+    /// added / removed / change alarm state, the search may return an error or fail to find a device
+    /// Device addresses will always be returned in the same order (lowest to highest, Little Endian)
+    fn devices<'a, 'b, D>(
+        &'a mut self,
+        only_alarming: bool,
+        delay: &'b mut D,
+    ) -> DeviceSearch<'a, 'b, Self, D>
+    where
+        Self: Sized,
+        D: DelayNs,
+    {
+        DeviceSearch {
+            onewire: self,
+            delay,
+            state: None,
+            finished: false,
+            only_alarming,
+        }
+    }
+
+    /// Search for device addresses on the bus
+    /// They can be filtered to only alarming devices if needed
+    /// Start the first search with a search_state of `None`, then use the returned state for subsequent searches
+    /// There is no time limit for continuing a search, but if devices are
+    /// added / removed / change alarm state, the search may return an error or fail to find a device
+    /// Device addresses will always be returned in the same order (lowest to highest, Little Endian)
+    fn device_search(
+        &mut self,
+        search_state: Option<&SearchState>,
+        only_alarming: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<Option<(Address, SearchState)>, Self::Error> {
+        if let Some(search_state) = search_state {
+            if search_state.discrepancies == 0 {
+                return Ok(None);
+            }
+        }
+
+        if !self.reset(delay)? {
+            return Ok(None);
+        }
+        if only_alarming {
+            self.write_byte(crate::commands::ALARM_SEARCH, delay)?;
+        } else {
+            self.write_byte(commands::SEARCH_NORMAL, delay)?;
+        }
+
+        // keep all discrepancies of the previous search except the last one, which is now explored
+        let mut discrepancies = match search_state {
+            Some(state) => state.discrepancies & !(1_u64 << state.last_discrepancy_index),
+            None => 0,
+        };
+        let mut address = 0;
+        for bit_index in 0..64_u8 {
+            let mask = 1_u64 << bit_index;
+            // follow the previous search up to its last discrepancy, then take the true branch there.
+            // False is always chosen first at new discrepancies
+            let (direction, is_new_bit) = match search_state {
+                Some(state) if bit_index < state.last_discrepancy_index => {
+                    (state.address & mask != 0, false)
+                }
+                Some(state) if bit_index == state.last_discrepancy_index => (true, false),
+                _ => (false, true),
+            };
+            let (id_bit, complement_bit, taken) = self.search_triplet(direction, delay)?;
+            match (id_bit, complement_bit) {
+                (true, true) if bit_index == 0 && search_state.is_none() => {
+                    // Nothing to find, e.g. an alarm search when no device is alarming
+                    return Ok(None);
+                }
+                (true, true) => {
+                    // No devices responded to the search request
+                    return Err(OneWireError::UnexpectedResponse);
+                }
+                (false, false) => {
+                    // Discrepancy, multiple values reported
+                    if is_new_bit {
+                        discrepancies |= mask;
+                    }
+                }
+                _ => {
+                    let was_last_discrepancy = matches!(search_state,
+                        Some(state) if bit_index == state.last_discrepancy_index);
+                    if was_last_discrepancy || (!is_new_bit && taken != direction) {
+                        // A different response was received than last search
+                        return Err(OneWireError::UnexpectedResponse);
+                    }
+                }
+            }
+            if taken {
+                address |= mask;
+            }
+        }
+        // index of the last (leftmost / closest to MSB) discrepancy bit
+        let last_discrepancy_index = match discrepancies {
+            0 => 0,
+            discrepancies => 63 - discrepancies.leading_zeros() as u8,
+        };
+        check_crc8(&address.to_le_bytes())?;
+        Ok(Some((
+            Address(address),
+            SearchState {
+                address,
+                discrepancies,
+                last_discrepancy_index,
+            },
+        )))
+    }
+}
+
+#[derive(Debug)]
+pub struct SearchState {
+    // The address of the last found device
+    address: u64,
+
+    // bitflags of discrepancies found
+    discrepancies: u64,
+
+    // index of the last (leftmost / closest to MSB) discrepancy bit. This can be calculated from the
+    // discrepancy bitflags, but it's cheaper to just save it. Index is an offset from the LSB
+    last_discrepancy_index: u8,
+}
+
+pub struct DeviceSearch<'a, 'b, B, D> {
+    onewire: &'a mut B,
+    delay: &'b mut D,
+    state: Option<SearchState>,
+    finished: bool,
+    only_alarming: bool,
+}
+
+impl<'a, 'b, B, E, D> Iterator for DeviceSearch<'a, 'b, B, D>
+where
+    B: OneWireMaster<Error = E>,
+    D: DelayNs,
+{
+    type Item = OneWireResult<Address, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result =
+            self.onewire
+                .device_search(self.state.as_ref(), self.only_alarming, self.delay);
+        match result {
+            Ok(Some((address, search_state))) => {
+                self.state = Some(search_state);
+                Some(Ok(address))
+            }
+            Ok(None) => {
+                self.state = None;
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.state = None;
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
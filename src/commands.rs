@@ -3,3 +3,9 @@ pub const WRITE_SCRATCHPAD: u8 = 0x4E;
 pub const READ_SCRATCHPAD: u8 = 0xBE;
 pub const COPY_SCRATCHPAD: u8 = 0x48;
 pub const RECALL_EEPROM: u8 = 0xB8;
+pub const ALARM_SEARCH: u8 = 0xEC;
+pub const READ_POWER_SUPPLY: u8 = 0xB4;
+pub const READ_ROM: u8 = 0x33;
+/// Undocumented: read the factory trim registers of genuine devices, which clones mostly ignore
+pub const READ_TRIM_1: u8 = 0x93;
+pub const READ_TRIM_2: u8 = 0x68;
@@ -0,0 +1,112 @@
+//! Adapters that let embedded-hal 0.2 pins and delays be used with this driver, during the transition to 1.0.
+//!
+//! ```ignore
+//! let mut one_wire_bus = OneWire::new(compat::Pin(pin))?;
+//! let mut delay = compat::Delay(delay);
+//! ```
+//!
+//! A bus from the `one-wire-bus` crate (built on embedded-hal 0.2) can also be used directly, since it
+//! implements `OneWireMaster`.
+
+use crate::bus::OneWireMaster;
+use core::fmt::Debug;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, ErrorKind, ErrorType, InputPin, OutputPin};
+use embedded_hal_02::blocking::delay::DelayUs;
+use embedded_hal_02::digital::v2;
+use one_wire_bus::OneWireResult;
+
+/// Wraps an embedded-hal 0.2 open-drain pin
+pub struct Pin<P>(pub P);
+
+/// Wraps the error of an embedded-hal 0.2 pin, since 1.0 requires pin errors to implement `digital::Error`
+#[derive(Debug, Copy, Clone)]
+pub struct PinError<E>(pub E);
+
+impl<E: Debug> digital::Error for PinError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl<P, E> ErrorType for Pin<P>
+where
+    P: v2::InputPin<Error = E>,
+    E: Debug,
+{
+    type Error = PinError<E>;
+}
+
+impl<P, E> InputPin for Pin<P>
+where
+    P: v2::InputPin<Error = E>,
+    E: Debug,
+{
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high().map_err(PinError)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low().map_err(PinError)
+    }
+}
+
+impl<P, E> OutputPin for Pin<P>
+where
+    P: v2::InputPin<Error = E>,
+    P: v2::OutputPin<Error = E>,
+    E: Debug,
+{
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low().map_err(PinError)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high().map_err(PinError)
+    }
+}
+
+/// Wraps an embedded-hal 0.2 microsecond delay
+pub struct Delay<D>(pub D);
+
+impl<D> DelayNs for Delay<D>
+where
+    D: DelayUs<u32>,
+{
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us);
+    }
+}
+
+/// Lets an embedded-hal 1.0 delay be used by the `one-wire-bus` crate
+struct DelayUs02<'a, D>(&'a mut D);
+
+impl<'a, D: DelayNs> DelayUs<u16> for DelayUs02<'a, D> {
+    fn delay_us(&mut self, us: u16) {
+        self.0.delay_us(u32::from(us));
+    }
+}
+
+impl<T, E> OneWireMaster for one_wire_bus::OneWire<T>
+where
+    T: v2::InputPin<Error = E>,
+    T: v2::OutputPin<Error = E>,
+{
+    type Error = E;
+
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, E> {
+        one_wire_bus::OneWire::reset(self, &mut DelayUs02(delay))
+    }
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, E> {
+        one_wire_bus::OneWire::read_bit(self, &mut DelayUs02(delay))
+    }
+
+    fn write_bit(&mut self, value: bool, delay: &mut impl DelayNs) -> OneWireResult<(), E> {
+        one_wire_bus::OneWire::write_bit(self, value, &mut DelayUs02(delay))
+    }
+}
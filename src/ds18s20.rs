@@ -0,0 +1,314 @@
+//! The DS18S20 (and the older DS1820), which has a fixed 9-bit temperature register, and
+//! COUNT_REMAIN/COUNT_PER_C registers for calculating a more precise temperature
+
+use crate::target::Target;
+use crate::{
+    Address, Ds18b20Error, Ds18b20Result, OneWireMaster, Reading, Scratchpad, StrongPullup,
+    Temperature,
+};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::delay::DelayNs;
+
+pub const FAMILY_CODE: u8 = 0x10;
+
+/// Conversions always take up to 750ms, since the resolution cannot be changed
+pub const MAX_MEASUREMENT_TIME_MILLIS: u16 = 750;
+
+/// Temperature register after power-up (85 °C, in 0.5 °C steps)
+pub(crate) const POWER_ON_TEMPERATURE: i16 = 0x00AA;
+
+/// Bytes 4-7 after power-up
+const POWER_ON_COUNT_BYTES: [u8; 4] = [0xFF, 0xFF, 0x0C, 0x10];
+
+/// All of the data that can be read from the sensor.
+#[derive(Debug)]
+pub struct SensorData {
+    /// Temperature calculated from COUNT_REMAIN and COUNT_PER_C, in 1/16 °C steps, or
+    /// `Reading::PowerOnReset` if the register still holds its power-on value
+    pub reading: Reading,
+
+    /// Temperature as read from the temperature register, in 0.5 °C steps
+    pub half_degree_reading: Reading,
+
+    /// If the last recorded temperature is lower than this, the sensor is put in an alarm state
+    pub alarm_temp_low: i8,
+
+    /// If the last recorded temperature is higher than this, the sensor is put in an alarm state
+    pub alarm_temp_high: i8,
+}
+
+pub struct Ds18s20 {
+    target: Target,
+}
+
+impl Ds18s20 {
+    /// Checks that the given address contains the DS18S20 family code, then returns a device
+    pub fn new<E>(address: Address) -> Ds18b20Result<Ds18s20, E> {
+        if address.family_code() == FAMILY_CODE {
+            Ok(Ds18s20 {
+                target: Target::new(Some(address)),
+            })
+        } else {
+            Err(Ds18b20Error::FamilyCodeMismatch(address.family_code()))
+        }
+    }
+
+    /// A device that is the only one on its bus, so it can be addressed with Skip ROM
+    pub fn single_on_bus() -> Ds18s20 {
+        Ds18s20 {
+            target: Target::new(None),
+        }
+    }
+
+    /// Starts a temperature measurement for just this device. In parasite power mode this blocks
+    /// until the measurement is finished, otherwise use `wait_for_conversion` or wait
+    /// `MAX_MEASUREMENT_TIME_MILLIS` before reading it
+    pub fn start_temp_measurement<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let millis = MAX_MEASUREMENT_TIME_MILLIS;
+        self.target
+            .start_conversion(millis, onewire, pullup, delay, || ())
+    }
+
+    /// Waits for a measurement started with `start_temp_measurement` to finish, by polling the bus.
+    /// This returns immediately in parasite power mode
+    pub fn wait_for_conversion<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.target
+            .wait_for_conversion(MAX_MEASUREMENT_TIME_MILLIS, onewire, delay)
+    }
+
+    pub fn read_data<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<SensorData, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let scratchpad = self.target.read_scratchpad(onewire, delay)?;
+        Ok(decode_scratchpad(&scratchpad))
+    }
+
+    /// Writes the alarm thresholds. There is no configuration register, so that is all there is to set
+    pub fn set_config<B, E>(
+        &self,
+        alarm_temp_low: i8,
+        alarm_temp_high: i8,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let bytes = [
+            alarm_temp_high.to_ne_bytes()[0],
+            alarm_temp_low.to_ne_bytes()[0],
+        ];
+        self.target.write_scratchpad(&bytes, onewire, delay)
+    }
+
+    /// Copies the alarm thresholds from the scratchpad to EEPROM, then recalls them to check that
+    /// they were written. Returns `EepromWriteUnverified` if they were not
+    pub fn save_to_eeprom<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.target.save_to_eeprom(onewire, pullup, delay)
+    }
+
+    pub fn recall_from_eeprom<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.target.recall_from_eeprom(onewire, delay)
+    }
+}
+
+impl Deref for Ds18s20 {
+    type Target = Target;
+
+    fn deref(&self) -> &Target {
+        &self.target
+    }
+}
+
+impl DerefMut for Ds18s20 {
+    fn deref_mut(&mut self) -> &mut Target {
+        &mut self.target
+    }
+}
+
+/// Decodes a DS18S20 scratchpad. The crc is not checked
+pub fn decode_scratchpad(scratchpad: &Scratchpad) -> SensorData {
+    let count_bytes = [
+        scratchpad.config,
+        scratchpad.reserved[0],
+        scratchpad.reserved[1],
+        scratchpad.reserved[2],
+    ];
+    let (reading, half_degree_reading) = if scratchpad.raw_temperature == POWER_ON_TEMPERATURE
+        && count_bytes == POWER_ON_COUNT_BYTES
+    {
+        (Reading::PowerOnReset, Reading::PowerOnReset)
+    } else {
+        let [_, _, count_remain, count_per_c] = count_bytes;
+        (
+            Reading::Temperature(extended_temperature(
+                scratchpad.raw_temperature,
+                count_remain,
+                count_per_c,
+            )),
+            Reading::Temperature(saturating_sixteenths(
+                i32::from(scratchpad.raw_temperature) * 8,
+            )),
+        )
+    };
+    SensorData {
+        reading,
+        half_degree_reading,
+        alarm_temp_high: scratchpad.alarm_temp_high,
+        alarm_temp_low: scratchpad.alarm_temp_low,
+    }
+}
+
+/// TEMPERATURE = TEMP_READ - 0.25 + (COUNT_PER_C - COUNT_REMAIN) / COUNT_PER_C, where TEMP_READ is the
+/// register with the 0.5 °C bit cleared. COUNT_PER_C is 16 on every device, so the result is in whole
+/// 1/16 °C steps
+fn extended_temperature(raw_temp: i16, count_remain: u8, count_per_c: u8) -> Temperature {
+    if count_per_c == 0 || count_remain > count_per_c {
+        return saturating_sixteenths(i32::from(raw_temp) * 8);
+    }
+    let count_per_c = i32::from(count_per_c);
+    let counted = 16 * (count_per_c - i32::from(count_remain)) / count_per_c;
+    saturating_sixteenths(i32::from(raw_temp & !1) * 8 - 4 + counted)
+}
+
+/// The register only holds -55 to 125 °C on a working device, but a corrupted one can be out of the
+/// range of `Temperature`, so the result is clamped
+fn saturating_sixteenths(sixteenths: i32) -> Temperature {
+    let clamped = sixteenths.clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+    Temperature::from_sixteenths(clamped as i16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temperature/data relationship table from the datasheet
+    const DATASHEET_TABLE: [(f32, u16); 7] = [
+        (85.0, 0x00AA),
+        (25.0, 0x0032),
+        (0.5, 0x0001),
+        (0.0, 0x0000),
+        (-0.5, 0xFFFF),
+        (-25.0, 0xFFCE),
+        (-55.0, 0xFF92),
+    ];
+
+    fn scratchpad(raw_temp: u16, count_remain: u8) -> Scratchpad {
+        let [lsb, msb] = raw_temp.to_le_bytes();
+        Scratchpad::from_bytes([lsb, msb, 75, 70, 0xFF, 0xFF, count_remain, 0x10, 0x00]).with_crc()
+    }
+
+    fn celsius(reading: Reading) -> f32 {
+        reading.temperature().unwrap().as_celsius_f32()
+    }
+
+    #[test]
+    fn decodes_datasheet_table() {
+        for &(expected, raw_temp) in DATASHEET_TABLE.iter() {
+            let data = decode_scratchpad(&scratchpad(raw_temp, 0x04));
+            assert_eq!(
+                celsius(data.half_degree_reading),
+                expected,
+                "raw value {:#06X}",
+                raw_temp
+            );
+        }
+    }
+
+    #[test]
+    fn calculates_extended_resolution() {
+        let cases = [
+            // 25.0 read, 25.0 - 0.25 + 12/16
+            (0x0032, 0x04, 25.5),
+            // 25.5 read, 25.0 - 0.25 + 2/16
+            (0x0033, 0x0E, 24.875),
+            (0x0032, 0x10, 24.75),
+            // -0.5 read, -1.0 - 0.25 + 9/16
+            (0xFFFF, 0x07, -0.6875),
+            (0xFF92, 0x0C, -55.0),
+        ];
+        for &(raw_temp, count_remain, expected) in cases.iter() {
+            let data = decode_scratchpad(&scratchpad(raw_temp, count_remain));
+            assert_eq!(
+                celsius(data.reading),
+                expected,
+                "raw value {:#06X}, COUNT_REMAIN {}",
+                raw_temp,
+                count_remain
+            );
+        }
+    }
+
+    #[test]
+    fn detects_power_on_reset() {
+        let data = decode_scratchpad(&scratchpad(0x00AA, 0x0C));
+        assert_eq!(data.reading, Reading::PowerOnReset);
+        assert_eq!(data.alarm_temp_high, 75);
+
+        let data = decode_scratchpad(&scratchpad(0x00AA, 0x0B));
+        assert_eq!(celsius(data.reading), 85.0625);
+    }
+
+    #[test]
+    fn clamps_out_of_range_registers() {
+        let data = decode_scratchpad(&scratchpad(0x1000, 0x0C));
+        assert_eq!(
+            data.reading.temperature().unwrap().as_sixteenths(),
+            i16::MAX
+        );
+        assert_eq!(
+            data.half_degree_reading
+                .temperature()
+                .unwrap()
+                .as_sixteenths(),
+            i16::MAX
+        );
+        let data = decode_scratchpad(&scratchpad(0x8000, 0x0C));
+        assert_eq!(
+            data.reading.temperature().unwrap().as_sixteenths(),
+            i16::MIN
+        );
+        assert_eq!(
+            data.half_degree_reading
+                .temperature()
+                .unwrap()
+                .as_sixteenths(),
+            i16::MIN
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! A `OneWireMaster` for the DS2482-100 and DS2482-800 I²C to 1-Wire bridges.
+//!
+//! The bridge generates the 1-Wire timing in hardware, so the delay passed to the bus operations is
+//! only used between polls of its busy flag. The -800 has 8 independent 1-Wire channels, see
+//! `Ds2482::select_channel`.
+//!
+//! ```ignore
+//! let mut bridge = Ds2482::new(i2c, ds2482::DEFAULT_ADDRESS);
+//! bridge.device_reset()?;
+//! bridge.set_active_pullup(true)?;
+//! let sensor_data = sensor.read_data(&mut bridge, &mut delay)?;
+//! ```
+//! https://www.analog.com/media/en/technical-documentation/data-sheets/DS2482-100.pdf
+
+use crate::bus::OneWireMaster;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use one_wire_bus::{OneWireError, OneWireResult};
+
+/// The I²C address with both address pins (AD0, AD1) low
+pub const DEFAULT_ADDRESS: u8 = 0x18;
+
+const DEVICE_RESET: u8 = 0xF0;
+const SET_READ_POINTER: u8 = 0xE1;
+const WRITE_CONFIGURATION: u8 = 0xD2;
+const CHANNEL_SELECT: u8 = 0xC3;
+const ONE_WIRE_RESET: u8 = 0xB4;
+const ONE_WIRE_SINGLE_BIT: u8 = 0x87;
+const ONE_WIRE_WRITE_BYTE: u8 = 0xA5;
+const ONE_WIRE_READ_BYTE: u8 = 0x96;
+const ONE_WIRE_TRIPLET: u8 = 0x78;
+
+const READ_DATA_REGISTER: u8 = 0xE1;
+
+const STATUS_BUSY: u8 = 1 << 0;
+const STATUS_PRESENCE: u8 = 1 << 1;
+const STATUS_SHORT: u8 = 1 << 2;
+const STATUS_DEVICE_RESET: u8 = 1 << 4;
+const STATUS_SINGLE_BIT: u8 = 1 << 5;
+const STATUS_TRIPLET_SECOND_BIT: u8 = 1 << 6;
+const STATUS_DIRECTION: u8 = 1 << 7;
+
+const CONFIG_ACTIVE_PULLUP: u8 = 1 << 0;
+const CONFIG_STRONG_PULLUP: u8 = 1 << 2;
+
+/// (code written, value read back) for each channel of the DS2482-800
+const CHANNELS: [(u8, u8); 8] = [
+    (0xF0, 0xB8),
+    (0xE1, 0xB1),
+    (0xD2, 0xAA),
+    (0xC3, 0xA3),
+    (0xB4, 0x9C),
+    (0xA5, 0x95),
+    (0x96, 0x8E),
+    (0x87, 0x87),
+];
+
+/// How many times the status register is read while waiting for a 1-Wire operation (a reset takes
+/// about 1.2ms, a byte about 0.6ms)
+const MAX_BUSY_POLLS: u16 = 100;
+const BUSY_POLL_INTERVAL_MICROS: u32 = 20;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Ds2482Error<E> {
+    I2c(E),
+
+    /// The bridge detected a short on the 1-Wire line during a reset
+    ShortDetected,
+
+    /// The bridge did not accept a configuration, device reset or channel selection
+    UnexpectedResponse,
+
+    /// Only channels 0 to 7 exist (and only on the DS2482-800)
+    InvalidChannel,
+}
+
+pub struct Ds2482<I2C> {
+    i2c: I2C,
+    address: u8,
+    config: u8,
+}
+
+impl<I2C, E> Ds2482<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Ds2482<I2C> {
+        Ds2482 {
+            i2c,
+            address,
+            config: 0,
+        }
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Resets the bridge (and terminates any 1-Wire communication). This clears the configuration
+    pub fn device_reset(&mut self) -> OneWireResult<(), Ds2482Error<E>> {
+        self.write(&[DEVICE_RESET])?;
+        let status = self.read()?;
+        if status & STATUS_DEVICE_RESET == 0 {
+            return Err(OneWireError::PinError(Ds2482Error::UnexpectedResponse));
+        }
+        self.config = 0;
+        Ok(())
+    }
+
+    /// Enables the active pull-up, which the datasheet recommends for all but the shortest buses
+    pub fn set_active_pullup(&mut self, enabled: bool) -> OneWireResult<(), Ds2482Error<E>> {
+        let config = if enabled {
+            self.config | CONFIG_ACTIVE_PULLUP
+        } else {
+            self.config & !CONFIG_ACTIVE_PULLUP
+        };
+        self.write_config(config)
+    }
+
+    /// Selects which 1-Wire channel (0 to 7) the following operations use. Only for the DS2482-800
+    pub fn select_channel(&mut self, channel: u8) -> OneWireResult<(), Ds2482Error<E>> {
+        let &(code, expected) = CHANNELS
+            .get(usize::from(channel))
+            .ok_or(OneWireError::PinError(Ds2482Error::InvalidChannel))?;
+        self.write(&[CHANNEL_SELECT, code])?;
+        if self.read()? != expected {
+            return Err(OneWireError::PinError(Ds2482Error::UnexpectedResponse));
+        }
+        Ok(())
+    }
+
+    fn write_config(&mut self, config: u8) -> OneWireResult<(), Ds2482Error<E>> {
+        // the upper nibble is the one's complement of the lower nibble
+        self.write(&[WRITE_CONFIGURATION, (config & 0x0F) | (!config << 4)])?;
+        if self.read()? != config & 0x0F {
+            return Err(OneWireError::PinError(Ds2482Error::UnexpectedResponse));
+        }
+        // the strong pull-up bit is cleared by the bridge once the strong pull-up ends
+        self.config = config & !CONFIG_STRONG_PULLUP;
+        Ok(())
+    }
+
+    /// Starts a 1-Wire operation, and waits for it to finish. Returns the status register
+    fn one_wire_command(
+        &mut self,
+        command: &[u8],
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<u8, Ds2482Error<E>> {
+        self.write(command)?;
+        for _ in 0..MAX_BUSY_POLLS {
+            let status = self.read()?;
+            if status & STATUS_BUSY == 0 {
+                return Ok(status);
+            }
+            delay.delay_us(BUSY_POLL_INTERVAL_MICROS);
+        }
+        Err(OneWireError::Timeout)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> OneWireResult<(), Ds2482Error<E>> {
+        self.i2c
+            .write(self.address, bytes)
+            .map_err(|err| OneWireError::PinError(Ds2482Error::I2c(err)))
+    }
+
+    /// Reads the register selected by the read pointer
+    fn read(&mut self) -> OneWireResult<u8, Ds2482Error<E>> {
+        let mut value = [0];
+        self.i2c
+            .read(self.address, &mut value)
+            .map_err(|err| OneWireError::PinError(Ds2482Error::I2c(err)))?;
+        Ok(value[0])
+    }
+}
+
+impl<I2C, E> OneWireMaster for Ds2482<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    type Error = Ds2482Error<E>;
+
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        let status = self.one_wire_command(&[ONE_WIRE_RESET], delay)?;
+        if status & STATUS_SHORT != 0 {
+            return Err(OneWireError::PinError(Ds2482Error::ShortDetected));
+        }
+        Ok(status & STATUS_PRESENCE != 0)
+    }
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        let status = self.one_wire_command(&[ONE_WIRE_SINGLE_BIT, 0x80], delay)?;
+        Ok(status & STATUS_SINGLE_BIT != 0)
+    }
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        let bit = if value { 0x80 } else { 0x00 };
+        self.one_wire_command(&[ONE_WIRE_SINGLE_BIT, bit], delay)?;
+        Ok(())
+    }
+
+    fn read_byte(&mut self, delay: &mut impl DelayNs) -> OneWireResult<u8, Self::Error> {
+        self.one_wire_command(&[ONE_WIRE_READ_BYTE], delay)?;
+        self.write(&[SET_READ_POINTER, READ_DATA_REGISTER])?;
+        self.read()
+    }
+
+    fn write_byte(
+        &mut self,
+        value: u8,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        self.one_wire_command(&[ONE_WIRE_WRITE_BYTE, value], delay)?;
+        Ok(())
+    }
+
+    fn search_triplet(
+        &mut self,
+        direction: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(bool, bool, bool), Self::Error> {
+        let direction = if direction { 0x80 } else { 0x00 };
+        let status = self.one_wire_command(&[ONE_WIRE_TRIPLET, direction], delay)?;
+        Ok((
+            status & STATUS_SINGLE_BIT != 0,
+            status & STATUS_TRIPLET_SECOND_BIT != 0,
+            status & STATUS_DIRECTION != 0,
+        ))
+    }
+
+    fn arm_strong_pullup(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        self.write_config(self.config | CONFIG_STRONG_PULLUP)?;
+        Ok(true)
+    }
+
+    fn disarm_strong_pullup(
+        &mut self,
+        _delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        self.write_config(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use core::convert::Infallible;
+    use embedded_hal::i2c::{ErrorType, Operation};
+    use std::vec::Vec;
+
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    #[derive(Copy, Clone)]
+    enum Register {
+        Status,
+        Data,
+        Config,
+        Channel,
+    }
+
+    /// A DS2482 that answers 1-Wire operations with a preset status
+    struct FakeBridge {
+        pointer: Register,
+        status: u8,
+        data: u8,
+        config: u8,
+        channel: u8,
+        /// Whether the bridge is a DS2482-800, which has the Channel Select command
+        has_channels: bool,
+        /// Status bits reported after the next 1-Wire operation
+        response: u8,
+        /// The byte the next 1-Wire Read Byte returns
+        byte: u8,
+        /// How many status reads show the bridge busy after a 1-Wire operation
+        busy_reads: u16,
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl FakeBridge {
+        fn new() -> FakeBridge {
+            FakeBridge {
+                pointer: Register::Status,
+                status: STATUS_DEVICE_RESET,
+                data: 0,
+                config: 0,
+                channel: CHANNELS[0].1,
+                has_channels: true,
+                response: 0,
+                byte: 0,
+                busy_reads: 0,
+                writes: Vec::new(),
+            }
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.writes.push(bytes.to_vec());
+            match *bytes {
+                [DEVICE_RESET] => {
+                    self.status = STATUS_DEVICE_RESET;
+                    self.config = 0;
+                    self.pointer = Register::Status;
+                }
+                [SET_READ_POINTER, register] => {
+                    self.pointer = match register {
+                        0xF0 => Register::Status,
+                        READ_DATA_REGISTER => Register::Data,
+                        0xC3 => Register::Config,
+                        0xD2 => Register::Channel,
+                        _ => panic!("no register {:#04X}", register),
+                    }
+                }
+                [WRITE_CONFIGURATION, config] => {
+                    if config >> 4 == !config & 0x0F {
+                        self.config = config & 0x0F;
+                    }
+                    self.pointer = Register::Config;
+                }
+                [CHANNEL_SELECT, code] if self.has_channels => {
+                    if let Some(&(_, value)) = CHANNELS.iter().find(|(c, _)| *c == code) {
+                        self.channel = value;
+                    }
+                    self.pointer = Register::Channel;
+                }
+                [command, ..] => {
+                    if command == ONE_WIRE_READ_BYTE {
+                        self.data = self.byte;
+                    }
+                    if command != CHANNEL_SELECT {
+                        // the strong pull-up only lasts for one operation
+                        self.config &= !CONFIG_STRONG_PULLUP;
+                    }
+                    self.status = self.response;
+                    self.pointer = Register::Status;
+                }
+                [] => {}
+            }
+        }
+
+        fn read(&mut self) -> u8 {
+            match self.pointer {
+                Register::Status if self.busy_reads > 0 => {
+                    self.busy_reads -= 1;
+                    self.status | STATUS_BUSY
+                }
+                Register::Status => self.status,
+                Register::Data => self.data,
+                Register::Config => self.config,
+                Register::Channel => self.channel,
+            }
+        }
+    }
+
+    impl ErrorType for FakeBridge {
+        type Error = Infallible;
+    }
+
+    impl I2c for FakeBridge {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Infallible> {
+            assert_eq!(address, DEFAULT_ADDRESS);
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.write(bytes),
+                    Operation::Read(buffer) => {
+                        for byte in buffer.iter_mut() {
+                            *byte = self.read();
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn bridge() -> Ds2482<FakeBridge> {
+        let mut bridge = Ds2482::new(FakeBridge::new(), DEFAULT_ADDRESS);
+        bridge.device_reset().unwrap();
+        bridge
+    }
+
+    #[test]
+    fn reset_reports_presence_and_shorts() {
+        let mut bridge = bridge();
+        bridge.i2c.response = STATUS_PRESENCE;
+        bridge.i2c.busy_reads = 3;
+        assert!(bridge.reset(&mut NoDelay).unwrap());
+
+        bridge.i2c.response = 0;
+        assert!(!bridge.reset(&mut NoDelay).unwrap());
+
+        bridge.i2c.response = STATUS_PRESENCE | STATUS_SHORT;
+        assert!(matches!(
+            bridge.reset(&mut NoDelay),
+            Err(OneWireError::PinError(Ds2482Error::ShortDetected))
+        ));
+
+        bridge.i2c.response = STATUS_PRESENCE;
+        bridge.i2c.busy_reads = MAX_BUSY_POLLS;
+        assert!(matches!(
+            bridge.reset(&mut NoDelay),
+            Err(OneWireError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn reads_bytes_from_the_data_register() {
+        let mut bridge = bridge();
+        bridge.i2c.byte = 0xA5;
+        bridge.i2c.busy_reads = 2;
+        assert_eq!(bridge.read_byte(&mut NoDelay).unwrap(), 0xA5);
+        let writes = &bridge.i2c.writes;
+        assert_eq!(
+            writes[writes.len() - 2..],
+            [
+                [ONE_WIRE_READ_BYTE].to_vec(),
+                [SET_READ_POINTER, READ_DATA_REGISTER].to_vec()
+            ]
+        );
+
+        // the next 1-Wire operation reads the status register again
+        bridge.i2c.response = STATUS_PRESENCE;
+        assert!(bridge.reset(&mut NoDelay).unwrap());
+    }
+
+    #[test]
+    fn decodes_single_bits_and_triplets() {
+        let mut bridge = bridge();
+        bridge.i2c.response = STATUS_SINGLE_BIT;
+        assert!(bridge.read_bit(&mut NoDelay).unwrap());
+        bridge.i2c.response = 0;
+        assert!(!bridge.read_bit(&mut NoDelay).unwrap());
+
+        bridge.i2c.response = STATUS_SINGLE_BIT | STATUS_DIRECTION;
+        assert_eq!(
+            bridge.search_triplet(true, &mut NoDelay).unwrap(),
+            (true, false, true)
+        );
+        assert_eq!(bridge.i2c.writes.last().unwrap(), &[ONE_WIRE_TRIPLET, 0x80]);
+
+        bridge.i2c.response = STATUS_TRIPLET_SECOND_BIT;
+        assert_eq!(
+            bridge.search_triplet(false, &mut NoDelay).unwrap(),
+            (false, true, false)
+        );
+        assert_eq!(bridge.i2c.writes.last().unwrap(), &[ONE_WIRE_TRIPLET, 0x00]);
+    }
+
+    #[test]
+    fn selects_channels() {
+        let mut bridge = bridge();
+        for (channel, &(code, _)) in CHANNELS.iter().enumerate() {
+            bridge.select_channel(channel as u8).unwrap();
+            assert_eq!(bridge.i2c.writes.last().unwrap(), &[CHANNEL_SELECT, code]);
+        }
+        assert!(matches!(
+            bridge.select_channel(8),
+            Err(OneWireError::PinError(Ds2482Error::InvalidChannel))
+        ));
+
+        // a DS2482-100 has no channels
+        bridge.i2c.has_channels = false;
+        assert!(matches!(
+            bridge.select_channel(1),
+            Err(OneWireError::PinError(Ds2482Error::UnexpectedResponse))
+        ));
+    }
+
+    #[test]
+    fn arms_and_disarms_the_strong_pullup() {
+        let mut bridge = bridge();
+        bridge.set_active_pullup(true).unwrap();
+        assert_eq!(
+            bridge.i2c.writes.last().unwrap(),
+            &[WRITE_CONFIGURATION, 0xE1]
+        );
+
+        assert!(bridge.arm_strong_pullup(&mut NoDelay).unwrap());
+        assert_eq!(
+            bridge.i2c.writes.last().unwrap(),
+            &[WRITE_CONFIGURATION, 0xA5]
+        );
+        assert_eq!(
+            bridge.i2c.config,
+            CONFIG_ACTIVE_PULLUP | CONFIG_STRONG_PULLUP
+        );
+
+        bridge.disarm_strong_pullup(&mut NoDelay).unwrap();
+        assert_eq!(
+            bridge.i2c.writes.last().unwrap(),
+            &[WRITE_CONFIGURATION, 0xE1]
+        );
+        assert_eq!(bridge.i2c.config, CONFIG_ACTIVE_PULLUP);
+    }
+}
@@ -0,0 +1,145 @@
+use core::fmt;
+use embedded_hal::digital;
+use one_wire_bus::OneWireError;
+
+pub type Ds18b20Result<T, E> = Result<T, Ds18b20Error<E>>;
+
+/// What went wrong when talking to a device. `E` is the error type of the bus master
+#[derive(Debug, Copy, Clone)]
+pub enum Ds18b20Error<E> {
+    /// The bus master failed, or the bus misbehaved (held low, or an unexpected search response)
+    Bus(OneWireError<E>),
+
+    /// No device answered the reset with a presence pulse
+    NotPresent,
+
+    /// The address belongs to a different device family
+    FamilyCodeMismatch(u8),
+
+    /// Every byte of the scratchpad read as 0xFF, which is what the bus looks like when nothing drives it,
+    /// e.g. when the device was disconnected in the middle of a read
+    AllOnes,
+
+    /// The scratchpad crc did not match, usually because of noise on the bus
+    CrcMismatch,
+
+    /// The configuration register holds a value that a DS18B20 cannot have
+    InvalidConfig(u8),
+
+    /// The device has the DS1825 family code, but is a MAX31850, which is read with `max31850::Max31850`
+    Max31850,
+
+    /// A conversion or EEPROM recall did not finish in time
+    Timeout,
+
+    /// The EEPROM did not hold the saved values when they were read back
+    EepromWriteUnverified,
+
+    /// The strong pull-up could not be enabled or disabled
+    StrongPullup(digital::ErrorKind),
+}
+
+/// Crc mismatches and timeouts have their own variants, every other bus error is wrapped in `Bus`
+impl<E> From<OneWireError<E>> for Ds18b20Error<E> {
+    fn from(err: OneWireError<E>) -> Ds18b20Error<E> {
+        match err {
+            OneWireError::CrcMismatch => Ds18b20Error::CrcMismatch,
+            OneWireError::Timeout => Ds18b20Error::Timeout,
+            err => Ds18b20Error::Bus(err),
+        }
+    }
+}
+
+impl<E> Ds18b20Error<E> {
+    /// Describes errors that carry no values
+    fn message(&self) -> &'static str {
+        match self {
+            Ds18b20Error::Bus(OneWireError::BusNotHigh) => "bus is not pulled high",
+            Ds18b20Error::Bus(OneWireError::PinError(_)) => "bus pin error",
+            Ds18b20Error::Bus(OneWireError::UnexpectedResponse) => "unexpected response on the bus",
+            Ds18b20Error::Bus(OneWireError::FamilyCodeMismatch) => "family code mismatch",
+            Ds18b20Error::Bus(OneWireError::CrcMismatch) | Ds18b20Error::CrcMismatch => {
+                "crc mismatch"
+            }
+            Ds18b20Error::Bus(OneWireError::Timeout) | Ds18b20Error::Timeout => "timed out",
+            Ds18b20Error::NotPresent => "no device present",
+            Ds18b20Error::FamilyCodeMismatch(_) => "family code mismatch",
+            Ds18b20Error::AllOnes => "scratchpad read as all ones, device not responding",
+            Ds18b20Error::InvalidConfig(_) => "invalid configuration register",
+            Ds18b20Error::Max31850 => "device is a MAX31850, not a DS1825",
+            Ds18b20Error::EepromWriteUnverified => "EEPROM does not hold the saved values",
+            Ds18b20Error::StrongPullup(_) => "strong pull-up error",
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Ds18b20Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ds18b20Error::Bus(OneWireError::PinError(err)) => {
+                write!(f, "{}: {:?}", self.message(), err)
+            }
+            Ds18b20Error::FamilyCodeMismatch(code) | Ds18b20Error::InvalidConfig(code) => {
+                write!(f, "{} (0x{:02X})", self.message(), code)
+            }
+            Ds18b20Error::StrongPullup(kind) => write!(f, "{}: {}", self.message(), kind),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for Ds18b20Error<E> {}
+
+#[cfg(feature = "defmt")]
+impl<E: defmt::Format> defmt::Format for Ds18b20Error<E> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Ds18b20Error::Bus(OneWireError::PinError(err)) => {
+                defmt::write!(f, "{}: {}", self.message(), err)
+            }
+            Ds18b20Error::FamilyCodeMismatch(code) | Ds18b20Error::InvalidConfig(code) => {
+                defmt::write!(f, "{} ({=u8:#04X})", self.message(), code)
+            }
+            Ds18b20Error::StrongPullup(kind) => {
+                defmt::write!(f, "{}: {}", self.message(), defmt::Debug2Format(kind))
+            }
+            _ => defmt::write!(f, "{}", self.message()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn keeps_specific_bus_errors_separate() {
+        let err: Ds18b20Error<()> = OneWireError::CrcMismatch.into();
+        assert!(matches!(err, Ds18b20Error::CrcMismatch));
+        let err: Ds18b20Error<()> = OneWireError::Timeout.into();
+        assert!(matches!(err, Ds18b20Error::Timeout));
+        let err: Ds18b20Error<()> = OneWireError::BusNotHigh.into();
+        assert!(matches!(err, Ds18b20Error::Bus(OneWireError::BusNotHigh)));
+    }
+
+    #[test]
+    fn displays_values() {
+        let err: Ds18b20Error<&str> = OneWireError::PinError("short").into();
+        assert_eq!(err.to_string(), "bus pin error: \"short\"");
+        let err: Ds18b20Error<()> = Ds18b20Error::FamilyCodeMismatch(0x10);
+        assert_eq!(err.to_string(), "family code mismatch (0x10)");
+        let err: Ds18b20Error<()> = Ds18b20Error::StrongPullup(digital::ErrorKind::Other);
+        assert_eq!(
+            err.to_string(),
+            "strong pull-up error: A different error occurred. The original error may contain more information"
+        );
+        let err: Ds18b20Error<()> = Ds18b20Error::AllOnes;
+        assert_eq!(
+            err.to_string(),
+            "scratchpad read as all ones, device not responding"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use crate::FAMILY_CODE;
+
+/// The devices that speak the DS18B20 protocol, told apart by the family code of their address
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SensorFamily {
+    Ds18b20,
+
+    /// A cheaper DS18B20, with ±2 °C accuracy
+    Ds1822,
+
+    /// A DS18B20 with 4 address pins (AD0-AD3), which are read from the low bits of the configuration
+    /// register, so a device can be told where it is mounted.
+    /// The MAX31850 has the same family code, see `max31850::is_max31850`
+    Ds1825,
+}
+
+impl SensorFamily {
+    pub fn from_family_code(family_code: u8) -> Option<SensorFamily> {
+        match family_code {
+            FAMILY_CODE => Some(SensorFamily::Ds18b20),
+            0x22 => Some(SensorFamily::Ds1822),
+            0x3B => Some(SensorFamily::Ds1825),
+            _ => None,
+        }
+    }
+
+    pub fn family_code(self) -> u8 {
+        match self {
+            SensorFamily::Ds18b20 => FAMILY_CODE,
+            SensorFamily::Ds1822 => 0x22,
+            SensorFamily::Ds1825 => 0x3B,
+        }
+    }
+
+    /// Splits a configuration register into the part that holds the resolution (as a DS18B20 would
+    /// have it), and the location set by the address pins of a DS1825
+    pub(crate) fn split_config(self, config: u8) -> (u8, Option<u8>) {
+        match self {
+            // bit 4 always reads as 1, the pins are in bits 0-3
+            SensorFamily::Ds1825 => (config | 0x0F, Some(config & 0x0F)),
+            SensorFamily::Ds18b20 | SensorFamily::Ds1822 => (config, None),
+        }
+    }
+}
@@ -1,24 +1,66 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! # Test Test
 
-use embedded_hal::blocking::delay::DelayUs;
-use embedded_hal::digital::v2::{InputPin, OutputPin};
-use one_wire_bus::{self, Address, OneWire, OneWireError, OneWireResult};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital;
 
 pub const FAMILY_CODE: u8 = 0x28;
 
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod bus;
 pub mod commands;
+#[cfg(feature = "embedded-hal-02")]
+pub mod compat;
+pub mod ds18s20;
+pub mod ds2482;
+mod error;
+mod family;
+pub mod max31850;
+mod measurement;
+pub mod onewire;
+mod power_mode;
 mod resolution;
+mod retry;
+mod scan;
+mod scratchpad;
+mod silicon;
+#[cfg(feature = "sim")]
+pub mod sim;
+mod strong_pullup;
+mod target;
+mod temperature;
+#[cfg(feature = "uart")]
+pub mod uart;
+#[cfg(feature = "std")]
+pub mod w1;
 
-use one_wire_bus::crc::check_crc8;
+pub use bus::OneWireMaster;
+use bus::SearchState;
+pub use error::{Ds18b20Error, Ds18b20Result};
+pub use family::SensorFamily;
+pub use measurement::{Clock, Instant, Measurement};
+use one_wire_bus::crc::crc8;
+pub use one_wire_bus::{Address, OneWireError, OneWireResult};
+pub use onewire::OneWire;
+pub use power_mode::PowerMode;
 pub use resolution::Resolution;
+pub use retry::RetryPolicy;
+pub use scan::{scan, Device};
+pub use scratchpad::Scratchpad;
+pub use silicon::{Quirks, Vendor};
+pub use strong_pullup::{NoStrongPullup, StrongPullup};
+pub use target::Target;
+pub use temperature::{Reading, Temperature};
 
 /// All of the data that can be read from the sensor.
 #[derive(Debug)]
 pub struct SensorData {
-    /// Temperature in degrees Celsius. Defaults to 85 on startup
-    pub temperature: f32,
+    /// Temperature in 1/16 °C steps, or `Reading::PowerOnReset` if no conversion has finished since
+    /// the device was powered up (the register reads 85 °C until then)
+    pub reading: Reading,
 
     /// The current resolution configuration
     pub resolution: Resolution,
@@ -28,220 +70,804 @@ pub struct SensorData {
 
     /// If the last recorded temperature is higher than this, the sensor is put in an alarm state
     pub alarm_temp_high: i8,
+
+    /// The location set by the address pins (AD0-AD3) of a DS1825, `None` for other families
+    pub location: Option<u8>,
 }
 
 pub struct Ds18b20 {
-    address: Address,
+    target: Target,
+    family: SensorFamily,
+    resolution: Resolution,
 }
 
 impl Ds18b20 {
-    /// Checks that the given address contains the correct family code, reads
-    /// configuration data, then returns a device
-    pub fn new<E>(address: Address) -> OneWireResult<Ds18b20, E> {
-        if address.family_code() == FAMILY_CODE {
-            Ok(Ds18b20 { address })
-        } else {
-            Err(OneWireError::FamilyCodeMismatch)
+    /// Checks that the given address contains the family code of a DS18B20, DS1822 or DS1825,
+    /// then returns a device. A MAX31850 has the DS1825 family code too, so it is accepted here, but
+    /// reading it fails with `Ds18b20Error::Max31850`. `scan` tells them apart up front
+    pub fn new<E>(address: Address) -> Ds18b20Result<Ds18b20, E> {
+        match SensorFamily::from_family_code(address.family_code()) {
+            Some(family) => Ok(Ds18b20::with_address(address, family)),
+            None => Err(Ds18b20Error::FamilyCodeMismatch(address.family_code())),
+        }
+    }
+
+    /// A device that is the only one on its bus, so it can be addressed with Skip ROM, without
+    /// knowing its address. Use `read_rom` if the address is needed.
+    /// The device is assumed to be a DS18B20, create it with `new` for the other families
+    pub fn single_on_bus() -> Ds18b20 {
+        Ds18b20 {
+            target: Target::new(None),
+            family: SensorFamily::Ds18b20,
+            resolution: Resolution::Bits12,
+        }
+    }
+
+    fn with_address(address: Address, family: SensorFamily) -> Ds18b20 {
+        Ds18b20 {
+            target: Target::new(Some(address)),
+            family,
+            resolution: Resolution::Bits12,
         }
     }
 
-    /// Returns the device address
-    pub fn address(&self) -> &Address {
-        &self.address
+    pub fn family(&self) -> SensorFamily {
+        self.family
+    }
+
+    /// Returns the resolution that conversions are timed for.
+    /// This is the slowest resolution (12 bits) until `set_config` or `set_resolution` is called
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Sets the resolution that conversions are timed for, when the device is already configured.
+    /// This does not change the device configuration, use `set_config` for that
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
     }
 
-    /// Starts a temperature measurement for just this device
-    /// You should wait for the measurement to finish before reading the measurement.
-    /// The amount of time you need to wait depends on the current resolution configuration
-    pub fn start_temp_measurement<T, E>(
+    /// Starts a temperature measurement for just this device, and returns without waiting for it.
+    /// The returned `Measurement` can be polled to read the result without blocking, or
+    /// `wait_for_conversion` blocks until it is finished.
+    ///
+    /// In parasite power mode the strong pull-up is turned on, and left on until the measurement is
+    /// polled after it has finished (or `wait_for_conversion` returns). Parasite mode is assumed until
+    /// `detect_power_mode` or `set_power_mode` says otherwise.
+    pub fn start_temp_measurement<B, E>(
         &self,
-        onewire: &mut OneWire<T>,
-        delay: &mut impl DelayUs<u16>,
-    ) -> OneWireResult<(), E>
+        onewire: &mut B,
+        clock: &impl Clock,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<Measurement, E>
     where
-        T: InputPin<Error = E>,
-        T: OutputPin<Error = E>,
+        B: OneWireMaster<Error = E>,
     {
-        onewire.send_command(commands::CONVERT_TEMP, Some(&self.address), delay)?;
-        Ok(())
+        let hold = self.target.begin_conversion(onewire, pullup, delay)?;
+        Ok(Measurement::new(
+            self.target.address,
+            self.family,
+            self.resolution,
+            clock.now(),
+            hold,
+            self.target.retry_policy,
+        ))
     }
 
-    pub fn read_data<T, E>(
+    /// Waits for a measurement started with `start_temp_measurement` to finish. Externally powered
+    /// devices are polled on the bus, and time out after the maximum measurement time for the resolution.
+    /// In parasite power mode this waits the maximum measurement time, then turns off the strong pull-up
+    pub fn wait_for_conversion<B, E>(
         &self,
-        onewire: &mut OneWire<T>,
-        delay: &mut impl DelayUs<u16>,
-    ) -> OneWireResult<SensorData, E>
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
     where
-        T: InputPin<Error = E>,
-        T: OutputPin<Error = E>,
+        B: OneWireMaster<Error = E>,
     {
-        let data = read_data(&self.address, onewire, delay)?;
-        Ok(data)
+        let millis = self.resolution.max_measurement_time_millis();
+        self.target
+            .finish_conversion(millis, onewire, pullup, delay)
     }
 
-    pub fn set_config<T, E>(
+    pub fn read_data<B, E>(
         &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<SensorData, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let scratchpad = self.target.read_scratchpad(onewire, delay)?;
+        decode_scratchpad(&scratchpad, self.family)
+    }
+
+    pub fn set_config<B, E>(
+        &mut self,
         alarm_temp_low: i8,
         alarm_temp_high: i8,
         resolution: Resolution,
-        onewire: &mut OneWire<T>,
-        delay: &mut impl DelayUs<u16>,
-    ) -> OneWireResult<(), E>
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
     where
-        T: InputPin<Error = E>,
-        T: OutputPin<Error = E>,
+        B: OneWireMaster<Error = E>,
     {
-        onewire.send_command(commands::WRITE_SCRATCHPAD, Some(&self.address), delay)?;
-        onewire.write_byte(alarm_temp_high.to_ne_bytes()[0], delay)?;
-        onewire.write_byte(alarm_temp_low.to_ne_bytes()[0], delay)?;
-        onewire.write_byte(resolution.to_config_register(), delay)?;
+        let bytes = [
+            alarm_temp_high.to_ne_bytes()[0],
+            alarm_temp_low.to_ne_bytes()[0],
+            resolution.to_config_register(),
+        ];
+        self.target.write_scratchpad(&bytes, onewire, delay)?;
+        self.resolution = resolution;
         Ok(())
     }
 
-    pub fn save_to_eeprom<T, E>(
+    /// Copies the alarm thresholds and resolution from the scratchpad to EEPROM, then recalls them to
+    /// check that they were written. Returns `EepromWriteUnverified` if they were not
+    pub fn save_to_eeprom<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.target.save_to_eeprom(onewire, pullup, delay)
+    }
+
+    pub fn recall_from_eeprom<B, E>(
         &self,
-        onewire: &mut OneWire<T>,
-        delay: &mut impl DelayUs<u16>,
-    ) -> OneWireResult<(), E>
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
     where
-        T: InputPin<Error = E>,
-        T: OutputPin<Error = E>,
+        B: OneWireMaster<Error = E>,
     {
-        save_to_eeprom(Some(&self.address), onewire, delay)
+        self.target.recall_from_eeprom(onewire, delay)
     }
 
-    pub fn recall_from_eeprom<T, E>(
+    /// Guesses who made the device, from the pattern of its address, the reserved bytes of its
+    /// scratchpad and whether it answers the undocumented Read Trim commands. Nothing is written to
+    /// the device. If the scratchpad cannot tell the vendors apart (the temperature register holds its
+    /// power-on value, or its LSB ends in 4), a conversion is run first, and a second one if that did
+    /// not settle it. In parasite power mode the conversions hold the strong pull-up. Returns
+    /// `Vendor::Unknown` if the scratchpad is still inconclusive, and for families other than the DS18B20
+    pub fn identify_silicon<B, E>(
         &self,
-        onewire: &mut OneWire<T>,
-        delay: &mut impl DelayUs<u16>,
-    ) -> OneWireResult<(), E>
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<Vendor, E>
     where
-        T: InputPin<Error = E>,
-        T: OutputPin<Error = E>,
+        B: OneWireMaster<Error = E>,
     {
-        recall_from_eeprom(Some(&self.address), onewire, delay)
+        // the fingerprints are only known for DS18B20 clones
+        if self.family != SensorFamily::Ds18b20 {
+            return Ok(Vendor::Unknown);
+        }
+        let address = match self.target.address {
+            Some(address) => address,
+            None => read_rom(onewire, delay)?,
+        };
+        let mut scratchpad = self.target.read_scratchpad(onewire, delay)?;
+        let millis = self.resolution.max_measurement_time_millis();
+        for _ in 0..FINGERPRINT_CONVERSIONS {
+            if Vendor::is_conclusive(&scratchpad) {
+                break;
+            }
+            self.target
+                .start_conversion(millis, onewire, pullup, delay, || ())?;
+            self.target.wait_for_conversion(millis, onewire, delay)?;
+            scratchpad = self.target.read_scratchpad(onewire, delay)?;
+        }
+        let vendor = Vendor::from_fingerprint(&address, &scratchpad);
+        if vendor != Vendor::Maxim {
+            return Ok(vendor);
+        }
+        let trim = self.target.retry(delay, |delay| {
+            read_trim(self.target.address(), onewire, delay)
+        })?;
+        Ok(vendor.check_trim(trim))
+    }
+}
+
+impl Deref for Ds18b20 {
+    type Target = Target;
+
+    fn deref(&self) -> &Target {
+        &self.target
+    }
+}
+
+impl DerefMut for Ds18b20 {
+    fn deref_mut(&mut self) -> &mut Target {
+        &mut self.target
     }
 }
 
+/// Conversions `identify_silicon` runs at most, for a scratchpad that cannot tell the vendors apart
+const FINGERPRINT_CONVERSIONS: u8 = 2;
+
 /// Starts a temperature measurement for all devices on this one-wire bus, simultaneously
-pub fn start_simultaneous_temp_measurement<T, E>(
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<(), E>
+///
+/// If any device is parasite powered (see `any_parasite_powered`), the strong pull-up is held for
+/// the conversion time of the given resolution, so this blocks until the measurement is finished.
+pub fn start_simultaneous_temp_measurement<B, E>(
+    onewire: &mut B,
+    power_mode: PowerMode,
+    resolution: Resolution,
+    pullup: &mut impl StrongPullup,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
-    onewire.reset(delay)?;
-    onewire.skip_address(delay)?;
-    onewire.write_byte(commands::CONVERT_TEMP, delay)?;
+    let master_pullup =
+        send_powered_command(commands::CONVERT_TEMP, None, power_mode, onewire, delay)?;
+    if power_mode == PowerMode::Parasite {
+        let millis = resolution.max_measurement_time_millis();
+        hold_strong_pullup(master_pullup, onewire, pullup, millis, delay)?;
+    }
     Ok(())
 }
 
+/// Waits for a temperature measurement to finish, by polling the bus instead of sleeping for the
+/// worst-case measurement time. Returns `Ds18b20Error::Timeout` if it takes longer than `timeout_millis`.
+///
+/// This must be called directly after starting a measurement (without any other commands in between).
+/// After a simultaneous measurement it returns once every device is done.
+/// Parasite powered devices cannot report their progress, so this only works for externally powered devices.
+pub fn wait_for_conversion<B, E>(
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+    timeout_millis: u16,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    wait_for_high_read_slot(onewire, delay, timeout_millis)
+}
+
+/// Returns an iterator over the devices whose last temperature measurement was outside of their
+/// alarm thresholds (using the Alarm Search command), identified the same way as by `scan`.
+/// Devices from other families are skipped, a 0x3B device that could not be read is `Device::Unknown`.
+/// The alarm flag is only updated by a temperature measurement, so start one (and wait for it to finish) first.
+pub fn alarming_devices<'a, 'b, B, E, D>(
+    onewire: &'a mut B,
+    delay: &'b mut D,
+) -> AlarmingDevices<'a, 'b, B, D>
+where
+    B: OneWireMaster<Error = E>,
+    D: DelayNs,
+{
+    AlarmingDevices {
+        onewire,
+        delay,
+        state: None,
+        finished: false,
+    }
+}
+
+pub struct AlarmingDevices<'a, 'b, B, D> {
+    onewire: &'a mut B,
+    delay: &'b mut D,
+    state: Option<SearchState>,
+    finished: bool,
+}
+
+impl<'a, 'b, B, E, D> Iterator for AlarmingDevices<'a, 'b, B, D>
+where
+    B: OneWireMaster<Error = E>,
+    D: DelayNs,
+{
+    type Item = Ds18b20Result<Device, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let found = self
+                .onewire
+                .device_search(self.state.as_ref(), true, self.delay);
+            let address = match found {
+                Ok(Some((address, state))) => {
+                    self.state = Some(state);
+                    address
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err.into()));
+                }
+            };
+            match scan::identify(address, self.onewire, self.delay) {
+                Ok(Device::Unknown(address))
+                    if SensorFamily::from_family_code(address.family_code()).is_none() =>
+                {
+                    continue
+                }
+                result => return Some(result),
+            }
+        }
+        self.finished = true;
+        None
+    }
+}
+
+/// Returns true if any device on the bus is powered from the data line.
+/// Every parasite powered device pulls the bus low in response, so a single read answers for the whole bus
+pub fn any_parasite_powered<B, E>(
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<bool, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    let power_mode = read_power_supply(None, onewire, delay)?;
+    Ok(power_mode == PowerMode::Parasite)
+}
+
 /// Read the contents of the EEPROM config to the scratchpad for all devices simultaneously.
-pub fn simultaneous_recall_from_eeprom<T, E>(
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<(), E>
+pub fn simultaneous_recall_from_eeprom<B, E>(
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
     recall_from_eeprom(None, onewire, delay)
 }
 
 /// Read the config contents of the scratchpad memory to the EEPROMfor all devices simultaneously.
-pub fn simultaneous_save_to_eeprom<T, E>(
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<(), E>
+pub fn simultaneous_save_to_eeprom<B, E>(
+    onewire: &mut B,
+    power_mode: PowerMode,
+    pullup: &mut impl StrongPullup,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
-    save_to_eeprom(None, onewire, delay)
+    save_to_eeprom(None, power_mode, onewire, pullup, delay)
 }
 
-pub fn read_scratchpad<T, E>(
-    address: &Address,
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<[u8; 9], E>
+/// Reads the scratchpad of a device, and checks that it was read correctly.
+/// Without an address, Skip ROM is used, which only works when the device is alone on the bus
+pub fn read_scratchpad<B, E>(
+    address: Option<&Address>,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<Scratchpad, E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
-    onewire.reset(delay)?;
-    onewire.match_address(address, delay)?;
-    onewire.write_byte(commands::READ_SCRATCHPAD, delay)?;
-    let mut scratchpad = [0; 9];
-    onewire.read_bytes(&mut scratchpad, delay)?;
-    check_crc8(&scratchpad)?;
+    send_command(commands::READ_SCRATCHPAD, address, onewire, delay)?;
+    let mut bytes = [0; 9];
+    onewire.read_bytes(&mut bytes, delay)?;
+    let scratchpad = Scratchpad::from_bytes(bytes);
+    scratchpad.validate()?;
     Ok(scratchpad)
 }
 
-fn read_data<T, E>(
-    address: &Address,
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<SensorData, E>
+/// Reads the address of the only device on the bus (using the Read ROM command). If there are more
+/// devices, they all answer at once and the crc will not match
+pub fn read_rom<B, E>(onewire: &mut B, delay: &mut impl DelayNs) -> Ds18b20Result<Address, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    if !onewire.reset(delay)? {
+        return Err(Ds18b20Error::NotPresent);
+    }
+    onewire.write_byte(commands::READ_ROM, delay)?;
+    let mut bytes = [0; 8];
+    onewire.read_bytes(&mut bytes, delay)?;
+    if bytes == [0xFF; 8] {
+        return Err(Ds18b20Error::AllOnes);
+    }
+    if crc8(&bytes) != 0 {
+        return Err(Ds18b20Error::CrcMismatch);
+    }
+    Ok(Address(u64::from_le_bytes(bytes)))
+}
+
+fn read_data<B, E>(
+    address: Option<&Address>,
+    family: SensorFamily,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<SensorData, E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
     let scratchpad = read_scratchpad(address, onewire, delay)?;
+    decode_scratchpad(&scratchpad, family)
+}
 
-    let resolution = if let Some(resolution) = Resolution::from_config_register(scratchpad[4]) {
+fn decode_scratchpad<E>(
+    scratchpad: &Scratchpad,
+    family: SensorFamily,
+) -> Ds18b20Result<SensorData, E> {
+    if family == SensorFamily::Ds1825 && max31850::is_max31850(scratchpad) {
+        return Err(Ds18b20Error::Max31850);
+    }
+    let (config, location) = family.split_config(scratchpad.config);
+    let resolution = if let Some(resolution) = Resolution::from_config_register(config) {
         resolution
     } else {
-        return Err(OneWireError::CrcMismatch);
+        return Err(Ds18b20Error::InvalidConfig(scratchpad.config));
     };
-    let raw_temp = u16::from_le_bytes([scratchpad[0], scratchpad[1]]);
-    let temperature = match resolution {
-        Resolution::Bits12 => (raw_temp as f32) / 16.0,
-        Resolution::Bits11 => (raw_temp as f32) / 8.0,
-        Resolution::Bits10 => (raw_temp as f32) / 4.0,
-        Resolution::Bits9 => (raw_temp as f32) / 2.0,
+    let reading = if scratchpad.is_power_on_reset() {
+        Reading::PowerOnReset
+    } else {
+        Reading::Temperature(Temperature::from_register(
+            scratchpad.raw_temperature,
+            resolution,
+        ))
     };
     Ok(SensorData {
-        temperature,
+        reading,
         resolution,
-        alarm_temp_high: i8::from_le_bytes([scratchpad[2]]),
-        alarm_temp_low: i8::from_le_bytes([scratchpad[3]]),
+        alarm_temp_high: scratchpad.alarm_temp_high,
+        alarm_temp_low: scratchpad.alarm_temp_low,
+        location,
     })
 }
 
-fn recall_from_eeprom<T, E>(
+fn read_power_supply<B, E>(
+    address: Option<&Address>,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<PowerMode, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    send_command(commands::READ_POWER_SUPPLY, address, onewire, delay)?;
+    // parasite powered devices pull the bus low during the read slot
+    let bit = onewire.read_bit(delay)?;
+    Ok(PowerMode::from_power_supply_bit(bit))
+}
+
+fn recall_from_eeprom<B, E>(
     address: Option<&Address>,
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<(), E>
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    send_command(commands::RECALL_EEPROM, address, onewire, delay)?;
+    wait_for_high_read_slot(onewire, delay, RECALL_MILLIS)
+}
+
+/// Time for a recall from EEPROM to finish
+const RECALL_MILLIS: u16 = 10;
+
+/// Issues read slots until a device reports that it is done (by not holding the bus low),
+/// which devices do while busy after some commands
+fn wait_for_high_read_slot<B, E>(
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+    timeout_millis: u16,
+) -> Ds18b20Result<(), E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
-    onewire.send_command(commands::RECALL_EEPROM, address, delay)?;
+    let mut poll = BusyPoll::new(timeout_millis);
+    while !poll.is_done(onewire, delay)? {
+        delay.delay_ms(BUSY_POLL_INTERVAL_MILLIS);
+    }
+    Ok(())
+}
 
-    // wait for the recall to finish (up to 10ms)
-    let max_retries = (10000 / one_wire_bus::READ_SLOT_DURATION_MICROS) + 1;
-    for _ in 0..max_retries {
-        if onewire.read_bit(delay)? == true {
-            return Ok(());
+/// Time between read slots while waiting for a device to finish
+const BUSY_POLL_INTERVAL_MILLIS: u32 = 1;
+
+/// Counts the read slots spent waiting for a busy device. The timeout is measured in the delays between
+/// them, so it does not depend on how long a read slot takes on the bus master
+struct BusyPoll {
+    remaining: u32,
+}
+
+impl BusyPoll {
+    fn new(timeout_millis: u16) -> BusyPoll {
+        BusyPoll {
+            remaining: u32::from(timeout_millis) / BUSY_POLL_INTERVAL_MILLIS,
+        }
+    }
+
+    /// Issues a read slot. Returns true if the device is done, or `Timeout` if it is still busy
+    /// and the time is up. Otherwise, wait `BUSY_POLL_INTERVAL_MILLIS` before polling again
+    fn is_done<B, E>(&mut self, onewire: &mut B, delay: &mut impl DelayNs) -> Ds18b20Result<bool, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        if onewire.read_bit(delay)? {
+            return Ok(true);
+        }
+        if self.remaining == 0 {
+            return Err(Ds18b20Error::Timeout);
+        }
+        self.remaining -= 1;
+        Ok(false)
+    }
+}
+
+fn save_to_eeprom<B, E>(
+    address: Option<&Address>,
+    power_mode: PowerMode,
+    onewire: &mut B,
+    pullup: &mut impl StrongPullup,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    let master_pullup = send_powered_command(
+        commands::COPY_SCRATCHPAD,
+        address,
+        power_mode,
+        onewire,
+        delay,
+    )?;
+    match power_mode {
+        PowerMode::Parasite => {
+            hold_strong_pullup(master_pullup, onewire, pullup, EEPROM_WRITE_MILLIS, delay)?
         }
+        PowerMode::External => delay.delay_ms(u32::from(EEPROM_WRITE_MILLIS)),
+    }
+    Ok(())
+}
+
+/// Checks that the scratchpad holds the same configuration as `saved`, after a recall from EEPROM
+fn verify_eeprom<B, E>(
+    saved: &Scratchpad,
+    address: Option<&Address>,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    let recalled = read_scratchpad(address, onewire, delay)?;
+    let configuration = |scratchpad: &Scratchpad| {
+        (
+            scratchpad.alarm_temp_high,
+            scratchpad.alarm_temp_low,
+            scratchpad.config,
+        )
+    };
+    if configuration(&recalled) != configuration(saved) {
+        return Err(Ds18b20Error::EepromWriteUnverified);
     }
-    Err(OneWireError::Timeout)
+    Ok(())
 }
 
-fn save_to_eeprom<T, E>(
+/// Time for the scratchpad to be copied to EEPROM
+const EEPROM_WRITE_MILLIS: u16 = 10;
+
+/// Resets the bus, then addresses one device (Match ROM) or all of them (Skip ROM).
+/// Returns `NotPresent` if no device answers the reset
+fn select<B, E>(
     address: Option<&Address>,
-    onewire: &mut OneWire<T>,
-    delay: &mut impl DelayUs<u16>,
-) -> OneWireResult<(), E>
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
 where
-    T: InputPin<Error = E>,
-    T: OutputPin<Error = E>,
+    B: OneWireMaster<Error = E>,
 {
-    onewire.send_command(commands::COPY_SCRATCHPAD, address, delay)?;
-    delay.delay_us(10000); // delay 10ms for the write to complete
+    if !onewire.reset(delay)? {
+        return Err(Ds18b20Error::NotPresent);
+    }
+    if let Some(address) = address {
+        onewire.match_address(address, delay)?;
+    } else {
+        onewire.skip_address(delay)?;
+    }
     Ok(())
 }
+
+/// Like `OneWireMaster::send_command`, but checks that a device is present
+/// Reads the two factory trim bytes with the undocumented Read Trim commands. Devices that do not
+/// implement them leave the bus high, so they read 0xFF
+fn read_trim<B, E>(
+    address: Option<&Address>,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<[u8; 2], E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    let mut trim = [0; 2];
+    for (byte, &command) in trim
+        .iter_mut()
+        .zip([commands::READ_TRIM_1, commands::READ_TRIM_2].iter())
+    {
+        send_command(command, address, onewire, delay)?;
+        *byte = onewire.read_byte(delay)?;
+    }
+    Ok(trim)
+}
+
+fn send_command<B, E>(
+    command: u8,
+    address: Option<&Address>,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    select(address, onewire, delay)?;
+    onewire.write_byte(command, delay)?;
+    Ok(())
+}
+
+fn pullup_error<P: digital::Error, E>(err: P) -> Ds18b20Error<E> {
+    Ds18b20Error::StrongPullup(err.kind())
+}
+
+/// Sends a command that parasite powered devices need extra power for. In parasite power mode the
+/// bus master's own strong pull-up is armed first, if it has one. Returns true if it was armed
+fn send_powered_command<B, E>(
+    command: u8,
+    address: Option<&Address>,
+    power_mode: PowerMode,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<bool, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    select(address, onewire, delay)?;
+    let master_pullup = power_mode == PowerMode::Parasite && onewire.arm_strong_pullup(delay)?;
+    onewire.write_byte(command, delay)?;
+    Ok(master_pullup)
+}
+
+/// Holds the bus high for `millis`, with either the master's strong pull-up (if it was armed) or the given one
+fn hold_strong_pullup<B, E>(
+    master_pullup: bool,
+    onewire: &mut B,
+    pullup: &mut impl StrongPullup,
+    millis: u16,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<(), E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    if master_pullup {
+        delay.delay_ms(u32::from(millis));
+        onewire.disarm_strong_pullup(delay)?;
+    } else {
+        pullup.enable().map_err(pullup_error)?;
+        delay.delay_ms(u32::from(millis));
+        pullup.disable().map_err(pullup_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temperature/data relationship table from the datasheet (12-bit resolution)
+    const DATASHEET_TABLE: [(f32, u16); 10] = [
+        (125.0, 0x07D0),
+        (85.0, 0x0550),
+        (25.0625, 0x0191),
+        (10.125, 0x00A2),
+        (0.5, 0x0008),
+        (0.0, 0x0000),
+        (-0.5, 0xFFF8),
+        (-10.125, 0xFF5E),
+        (-25.0625, 0xFE6F),
+        (-55.0, 0xFC90),
+    ];
+
+    /// A scratchpad after a conversion, with byte 6 set the way genuine devices set it
+    fn scratchpad(raw_temp: u16, resolution: Resolution) -> [u8; 9] {
+        let [lsb, msb] = raw_temp.to_le_bytes();
+        [
+            lsb,
+            msb,
+            75,
+            70,
+            resolution.to_config_register(),
+            0xFF,
+            0x10 - (lsb & 0x0F),
+            0x10,
+            0x00,
+        ]
+    }
+
+    fn temperature(data: &SensorData) -> f32 {
+        data.reading.temperature().unwrap().as_celsius_f32()
+    }
+
+    #[test]
+    fn decodes_datasheet_table() {
+        for &(expected, raw_temp) in DATASHEET_TABLE.iter() {
+            let data = decode_scratchpad::<()>(
+                &scratchpad(raw_temp, Resolution::Bits12).into(),
+                SensorFamily::Ds18b20,
+            )
+            .unwrap();
+            assert_eq!(temperature(&data), expected, "raw value {:#06X}", raw_temp);
+        }
+    }
+
+    #[test]
+    fn masks_undefined_bits_at_lower_resolutions() {
+        let cases = [
+            (Resolution::Bits11, 0x0191, 25.0),
+            (Resolution::Bits10, 0x00A2, 10.0),
+            (Resolution::Bits9, 0x0191, 25.0),
+            (Resolution::Bits11, 0xFF5E, -10.125),
+            (Resolution::Bits10, 0xFE6F, -25.25),
+            (Resolution::Bits9, 0xFF5E, -10.5),
+            (Resolution::Bits9, 0xFFF8, -0.5),
+        ];
+        for &(resolution, raw_temp, expected) in cases.iter() {
+            let data = decode_scratchpad::<()>(
+                &scratchpad(raw_temp, resolution).into(),
+                SensorFamily::Ds18b20,
+            )
+            .unwrap();
+            assert_eq!(
+                temperature(&data),
+                expected,
+                "raw value {:#06X} at {:?}",
+                raw_temp,
+                resolution
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_alarm_thresholds_as_signed() {
+        let mut bytes = scratchpad(0x0000, Resolution::Bits12);
+        bytes[2] = 0x19;
+        bytes[3] = 0xF6;
+        let data = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds18b20).unwrap();
+        assert_eq!(data.alarm_temp_high, 25);
+        assert_eq!(data.alarm_temp_low, -10);
+    }
+
+    #[test]
+    fn detects_power_on_reset() {
+        let mut bytes = scratchpad(0x0550, Resolution::Bits12);
+        let data = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds18b20).unwrap();
+        assert_eq!(
+            data.reading,
+            Reading::Temperature(Temperature::from_degrees(85))
+        );
+
+        bytes[6] = 0x0C;
+        let data = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds18b20).unwrap();
+        assert_eq!(data.reading, Reading::PowerOnReset);
+        assert_eq!(data.reading.temperature(), None);
+    }
+
+    #[test]
+    fn decodes_ds1825_location_pins() {
+        let mut bytes = scratchpad(0x0191, Resolution::Bits10);
+        // bit 4 reads as 1, the address pins are in bits 0-3
+        bytes[4] = 0b0011_0110;
+        let data = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds1825).unwrap();
+        assert_eq!(data.resolution, Resolution::Bits10);
+        assert_eq!(data.location, Some(0b0110));
+
+        let result = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds18b20);
+        assert!(matches!(result, Err(Ds18b20Error::InvalidConfig(0x36))));
+    }
+
+    #[test]
+    fn rejects_invalid_config_register() {
+        let mut bytes = scratchpad(0x0191, Resolution::Bits12);
+        bytes[4] = 0xFF;
+        let result = decode_scratchpad::<()>(&bytes.into(), SensorFamily::Ds18b20);
+        assert!(matches!(result, Err(Ds18b20Error::InvalidConfig(0xFF))));
+    }
+}
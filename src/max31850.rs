@@ -0,0 +1,227 @@
+//! The MAX31850/MAX31851 thermocouple-to-1-Wire converters. They share the family code of the DS1825,
+//! and answer Convert T and Read Scratchpad like a DS18B20, but have no EEPROM or alarm thresholds
+
+use crate::target::Target;
+use crate::{
+    Address, Ds18b20Error, Ds18b20Result, OneWireMaster, Scratchpad, StrongPullup, Temperature,
+};
+use core::ops::{Deref, DerefMut};
+use embedded_hal::delay::DelayNs;
+
+pub const FAMILY_CODE: u8 = 0x3B;
+
+pub const MAX_MEASUREMENT_TIME_MILLIS: u16 = 100;
+
+/// A problem with the thermocouple, which makes its temperature meaningless
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// No thermocouple is connected
+    OpenCircuit,
+
+    /// The thermocouple is shorted to ground
+    ShortToGround,
+
+    /// The thermocouple is shorted to the supply voltage
+    ShortToVcc,
+}
+
+/// All of the data that can be read from the converter.
+#[derive(Debug)]
+pub struct SensorData {
+    /// Temperature at the tip of the thermocouple, in 0.25 °C steps
+    pub thermocouple: Temperature,
+
+    /// Temperature of the converter itself, in 1/16 °C steps
+    pub cold_junction: Temperature,
+
+    /// Set if the thermocouple temperature could not be measured
+    pub fault: Option<Fault>,
+
+    /// The location set by the address pins (AD0-AD3)
+    pub location: u8,
+}
+
+impl SensorData {
+    /// Returns the thermocouple temperature, or the fault that prevented measuring it
+    pub fn temperature(&self) -> Result<Temperature, Fault> {
+        match self.fault {
+            Some(fault) => Err(fault),
+            None => Ok(self.thermocouple),
+        }
+    }
+}
+
+pub struct Max31850 {
+    target: Target,
+}
+
+impl Max31850 {
+    /// Checks that the given address contains the MAX31850 family code, then returns a device.
+    /// The DS1825 has the same family code, use `is_max31850` on its scratchpad to tell them apart
+    pub fn new<E>(address: Address) -> Ds18b20Result<Max31850, E> {
+        if address.family_code() == FAMILY_CODE {
+            Ok(Max31850 {
+                target: Target::new(Some(address)),
+            })
+        } else {
+            Err(Ds18b20Error::FamilyCodeMismatch(address.family_code()))
+        }
+    }
+
+    /// A device that is the only one on its bus, so it can be addressed with Skip ROM
+    pub fn single_on_bus() -> Max31850 {
+        Max31850 {
+            target: Target::new(None),
+        }
+    }
+
+    /// Starts a measurement of both temperatures. In parasite power mode this blocks until the
+    /// measurement is finished, otherwise use `wait_for_conversion` or wait
+    /// `MAX_MEASUREMENT_TIME_MILLIS` before reading it
+    pub fn start_temp_measurement<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let millis = MAX_MEASUREMENT_TIME_MILLIS;
+        self.target
+            .start_conversion(millis, onewire, pullup, delay, || ())
+    }
+
+    /// Waits for a measurement started with `start_temp_measurement` to finish, by polling the bus.
+    /// This returns immediately in parasite power mode
+    pub fn wait_for_conversion<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.target
+            .wait_for_conversion(MAX_MEASUREMENT_TIME_MILLIS, onewire, delay)
+    }
+
+    /// Reads the result of the last measurement. A thermocouple fault is not an error, it is
+    /// reported in `SensorData::fault`
+    pub fn read_data<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<SensorData, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let scratchpad = self.target.read_scratchpad(onewire, delay)?;
+        Ok(decode_scratchpad(&scratchpad))
+    }
+}
+
+impl Deref for Max31850 {
+    type Target = Target;
+
+    fn deref(&self) -> &Target {
+        &self.target
+    }
+}
+
+impl DerefMut for Max31850 {
+    fn deref_mut(&mut self) -> &mut Target {
+        &mut self.target
+    }
+}
+
+/// True if the scratchpad comes from a MAX31850 rather than a DS1825. The upper 4 bits of its
+/// configuration register always read as 1, while bit 7 of a DS1825 always reads as 0
+pub fn is_max31850(scratchpad: &Scratchpad) -> bool {
+    scratchpad.config & 0xF0 == 0xF0
+}
+
+/// Decodes a MAX31850 scratchpad. The crc is not checked
+pub fn decode_scratchpad(scratchpad: &Scratchpad) -> SensorData {
+    let bytes = scratchpad.to_bytes();
+    // bits 0 and 1 are the fault flag and a reserved bit, the rest is the temperature in 0.25 °C steps
+    let thermocouple = i16::from_le_bytes([bytes[0], bytes[1]]) & !0b11;
+    // the low 4 bits hold the fault flags, the rest is the temperature in 1/16 °C steps
+    let cold_junction = i16::from_le_bytes([bytes[2], bytes[3]]) >> 4;
+    let fault = if bytes[0] & 0x01 == 0 {
+        None
+    } else if bytes[2] & 0x01 != 0 {
+        Some(Fault::OpenCircuit)
+    } else if bytes[2] & 0x02 != 0 {
+        Some(Fault::ShortToGround)
+    } else if bytes[2] & 0x04 != 0 {
+        Some(Fault::ShortToVcc)
+    } else {
+        None
+    };
+    SensorData {
+        thermocouple: Temperature::from_sixteenths(thermocouple),
+        cold_junction: Temperature::from_sixteenths(cold_junction),
+        fault,
+        location: scratchpad.config & 0x0F,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratchpad(thermocouple: u16, cold_junction: u16, location: u8) -> Scratchpad {
+        let [tc_lsb, tc_msb] = thermocouple.to_le_bytes();
+        let [cj_lsb, cj_msb] = cold_junction.to_le_bytes();
+        let config = 0xF0 | location;
+        Scratchpad::from_bytes([
+            tc_lsb, tc_msb, cj_lsb, cj_msb, config, 0xFF, 0xFF, 0xFF, 0x00,
+        ])
+        .with_crc()
+    }
+
+    #[test]
+    fn decodes_datasheet_values() {
+        // thermocouple: +1600 °C, +100.75 °C, -0.25 °C, -250 °C
+        // cold junction: +127 °C, +25 °C, -0.0625 °C, -55 °C
+        let cases = [
+            (0x6400, 1600.0, 0x7F00, 127.0),
+            (0x064C, 100.75, 0x1900, 25.0),
+            (0xFFFC, -0.25, 0xFFF0, -0.0625),
+            (0xF060, -250.0, 0xC900, -55.0),
+        ];
+        for &(thermocouple, expected_tc, cold_junction, expected_cj) in cases.iter() {
+            let data = decode_scratchpad(&scratchpad(thermocouple, cold_junction, 0));
+            assert_eq!(data.thermocouple.as_celsius_f32(), expected_tc);
+            assert_eq!(data.cold_junction.as_celsius_f32(), expected_cj);
+            assert_eq!(data.temperature(), Ok(data.thermocouple));
+        }
+    }
+
+    #[test]
+    fn decodes_faults() {
+        let cases = [
+            (0x01, Fault::OpenCircuit),
+            (0x02, Fault::ShortToGround),
+            (0x04, Fault::ShortToVcc),
+        ];
+        for &(flag, fault) in cases.iter() {
+            let data = decode_scratchpad(&scratchpad(0x0001, 0x1900 | flag, 0));
+            assert_eq!(data.fault, Some(fault));
+            assert_eq!(data.temperature(), Err(fault));
+            assert_eq!(data.cold_junction.as_celsius_f32(), 25.0);
+        }
+    }
+
+    #[test]
+    fn tells_apart_ds1825() {
+        let max31850 = scratchpad(0x064C, 0x1900, 0b0101);
+        assert!(is_max31850(&max31850));
+        assert_eq!(decode_scratchpad(&max31850).location, 0b0101);
+
+        let mut ds1825 = max31850;
+        ds1825.config = 0b0111_0101;
+        assert!(!is_max31850(&ds1825));
+    }
+}
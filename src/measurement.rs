@@ -0,0 +1,130 @@
+use crate::{
+    pullup_error, read_data, Address, Ds18b20Error, Ds18b20Result, OneWireMaster, Resolution,
+    RetryPolicy, SensorData, SensorFamily, StrongPullup,
+};
+use core::cell::Cell;
+use embedded_hal::delay::DelayNs;
+
+/// A point in time, in milliseconds since an arbitrary starting point. The value is allowed to wrap.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Instant(pub u32);
+
+impl Instant {
+    /// Milliseconds elapsed since `earlier`, accounting for wrap-around
+    pub fn millis_since(self, earlier: Instant) -> u32 {
+        self.0.wrapping_sub(earlier.0)
+    }
+}
+
+/// A monotonic clock, used to time measurements without blocking
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// A temperature measurement that has been started, but may not be finished yet.
+///
+/// In parasite power mode (which is assumed until the power mode is detected or set) the strong
+/// pull-up is left on while the device converts, and `poll` turns it off once the measurement is finished.
+/// Until then, nothing else can use the bus
+#[derive(Copy, Clone, Debug)]
+pub struct Measurement {
+    address: Option<Address>,
+    family: SensorFamily,
+    resolution: Resolution,
+    started: Instant,
+    pullup: PullupHold,
+    retry_policy: RetryPolicy,
+}
+
+/// What holds the bus high while a parasite powered device converts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PullupHold {
+    /// The device is externally powered
+    None,
+
+    /// The bus master's own strong pull-up, armed with `OneWireMaster::arm_strong_pullup`
+    Master,
+
+    /// The `StrongPullup` passed to the driver
+    External,
+}
+
+impl PullupHold {
+    pub(crate) fn release<B, E>(
+        self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        match self {
+            PullupHold::None => Ok(()),
+            PullupHold::Master => Ok(onewire.disarm_strong_pullup(delay)?),
+            PullupHold::External => pullup.disable().map_err(pullup_error),
+        }
+    }
+}
+
+impl Measurement {
+    pub(crate) fn new(
+        address: Option<Address>,
+        family: SensorFamily,
+        resolution: Resolution,
+        started: Instant,
+        pullup: PullupHold,
+        retry_policy: RetryPolicy,
+    ) -> Measurement {
+        Measurement {
+            address,
+            family,
+            resolution,
+            started,
+            pullup,
+            retry_policy,
+        }
+    }
+
+    /// The time the measurement was started
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// The resolution the measurement is timed for
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Returns true once the maximum measurement time for the resolution has passed
+    pub fn is_ready(&self, now: Instant) -> bool {
+        now.millis_since(self.started) >= u32::from(self.resolution.max_measurement_time_millis())
+    }
+
+    /// Reads the result if the measurement is finished, or returns `WouldBlock` without touching the bus.
+    /// In parasite power mode the strong pull-up is turned off first, so `pullup` has to be the one
+    /// the measurement was started with. The read is retried as the device's retry policy said when the
+    /// measurement was started, so this only blocks for the read itself and any retries.
+    pub fn poll<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+        now: Instant,
+    ) -> nb::Result<SensorData, Ds18b20Error<E>>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        if !self.is_ready(now) {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.pullup
+            .release(onewire, pullup, delay)
+            .map_err(nb::Error::Other)?;
+        self.retry_policy
+            .run(&Cell::new(0), delay, |delay| {
+                read_data(self.address.as_ref(), self.family, onewire, delay)
+            })
+            .map_err(nb::Error::Other)
+    }
+}
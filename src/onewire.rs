@@ -0,0 +1,125 @@
+//! Implementation of the 1-Wire protocol, bit-banged on a single open-drain pin.
+//! https://www.maximintegrated.com/en/design/technical-documents/app-notes/1/126.html
+//!
+//! This follows the `one-wire-bus` crate (and reuses its `Address`, error and crc types), but is
+//! written against the embedded-hal 1.0 traits.
+
+use crate::bus::OneWireMaster;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use one_wire_bus::{OneWireError, OneWireResult};
+
+pub use one_wire_bus::commands;
+
+pub const READ_SLOT_DURATION_MICROS: u16 = 70;
+
+pub struct OneWire<T> {
+    pin: T,
+}
+
+impl<T, E> OneWire<T>
+where
+    T: InputPin<Error = E>,
+    T: OutputPin<Error = E>,
+{
+    pub fn new(pin: T) -> OneWireResult<OneWire<T>, E> {
+        let mut one_wire = OneWire { pin };
+        // Pin should be high during idle.
+        one_wire.release_bus()?;
+        Ok(one_wire)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.pin
+    }
+
+    /// Disconnects the bus, letting another device (or the pull-up resistor) set the bus value
+    pub fn release_bus(&mut self) -> OneWireResult<(), E> {
+        self.pin.set_high().map_err(OneWireError::PinError)
+    }
+
+    /// Drives the bus low
+    pub fn set_bus_low(&mut self) -> OneWireResult<(), E> {
+        self.pin.set_low().map_err(OneWireError::PinError)
+    }
+
+    pub fn is_bus_high(&mut self) -> OneWireResult<bool, E> {
+        self.pin.is_high().map_err(OneWireError::PinError)
+    }
+
+    pub fn is_bus_low(&mut self) -> OneWireResult<bool, E> {
+        self.pin.is_low().map_err(OneWireError::PinError)
+    }
+
+    fn wait_for_high(&mut self, delay: &mut impl DelayNs) -> OneWireResult<(), E> {
+        // wait up to 250 µs for the bus to become high (from the pull-up resistor)
+        for _ in 0..125 {
+            if self.is_bus_high()? {
+                return Ok(());
+            }
+            delay.delay_us(2);
+        }
+        Err(OneWireError::BusNotHigh)
+    }
+
+    pub fn write_1_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<(), E> {
+        self.set_bus_low()?;
+        delay.delay_us(6); // Maxim recommended wait time
+
+        self.release_bus()?;
+        delay.delay_us(64); // Maxim recommended wait time
+        Ok(())
+    }
+
+    pub fn write_0_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<(), E> {
+        self.set_bus_low()?;
+        delay.delay_us(60); // Maxim recommended wait time
+
+        self.release_bus()?;
+        delay.delay_us(10); // Maxim recommended wait time
+        Ok(())
+    }
+}
+
+impl<T, E> OneWireMaster for OneWire<T>
+where
+    T: InputPin<Error = E>,
+    T: OutputPin<Error = E>,
+{
+    type Error = E;
+
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, E> {
+        self.wait_for_high(delay)?;
+
+        self.set_bus_low()?;
+        delay.delay_us(480); // Maxim recommended wait time
+
+        self.release_bus()?;
+        delay.delay_us(70); // Maxim recommended wait time
+
+        let device_present = self.is_bus_low()?;
+
+        delay.delay_us(410); // Maxim recommended wait time
+        Ok(device_present)
+    }
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, E> {
+        self.set_bus_low()?;
+        delay.delay_us(6); // Maxim recommended wait time
+
+        self.release_bus()?;
+        delay.delay_us(9); // Maxim recommended wait time
+
+        let bit_value = self.is_bus_high()?;
+        delay.delay_us(55); // Maxim recommended wait time
+        Ok(bit_value)
+    }
+
+    fn write_bit(&mut self, value: bool, delay: &mut impl DelayNs) -> OneWireResult<(), E> {
+        if value {
+            self.write_1_bit(delay)
+        } else {
+            self.write_0_bit(delay)
+        }
+    }
+}
@@ -0,0 +1,33 @@
+/// How a device is powered, as reported by the Read Power Supply command
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerMode {
+    /// Powered through the VDD pin. The device can signal when a conversion or
+    /// EEPROM recall has finished
+    External,
+
+    /// Powered from the data line. The bus has to be held high during conversions and EEPROM
+    /// writes, and the device cannot report its progress
+    Parasite,
+}
+
+impl PowerMode {
+    /// Parasite mode is assumed until the power mode is detected, since the
+    /// parasite-safe behaviour also works for externally powered devices
+    pub const fn assumed() -> PowerMode {
+        PowerMode::Parasite
+    }
+
+    pub(crate) fn from_power_supply_bit(bit: bool) -> PowerMode {
+        if bit {
+            PowerMode::External
+        } else {
+            PowerMode::Parasite
+        }
+    }
+}
+
+impl Default for PowerMode {
+    fn default() -> PowerMode {
+        PowerMode::assumed()
+    }
+}
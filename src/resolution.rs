@@ -1,7 +1,7 @@
-use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::delay::DelayNs;
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Resolution {
     Bits9 = 0b00011111,
     Bits10 = 0b00111111,
@@ -21,8 +21,8 @@ impl Resolution {
 
     /// Blocks for the amount of time required to finished measuring temperature
     /// using this resolution
-    pub fn delay_for_measurement_time(&self, delay: &mut impl DelayMs<u16>) {
-        delay.delay_ms(self.max_measurement_time_millis());
+    pub fn delay_for_measurement_time(&self, delay: &mut impl DelayNs) {
+        delay.delay_ms(u32::from(self.max_measurement_time_millis()));
     }
 
     pub(crate) fn from_config_register(config: u8) -> Option<Resolution> {
@@ -35,7 +35,7 @@ impl Resolution {
         }
     }
 
-    pub(crate) fn to_config_register(&self) -> u8 {
-        *self as u8
+    pub(crate) fn to_config_register(self) -> u8 {
+        self as u8
     }
 }
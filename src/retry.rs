@@ -0,0 +1,113 @@
+use crate::{Ds18b20Error, Ds18b20Result};
+use core::cell::Cell;
+use embedded_hal::delay::DelayNs;
+
+/// How often an operation is retried when it fails with a transient error, such as noise on a long cable
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first one. 1 disables retrying
+    pub attempts: u8,
+
+    /// Time to wait before each retry
+    pub backoff_millis: u32,
+
+    /// Retry when the crc does not match
+    pub on_crc_mismatch: bool,
+
+    /// Retry when no device answers the reset, or the scratchpad reads as all ones
+    pub on_not_present: bool,
+
+    /// Retry when a conversion or EEPROM recall takes too long
+    pub on_timeout: bool,
+}
+
+impl RetryPolicy {
+    /// Every operation is tried once
+    pub const NONE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        backoff_millis: 0,
+        on_crc_mismatch: false,
+        on_not_present: false,
+        on_timeout: false,
+    };
+
+    /// Retries crc mismatches and missing devices, up to `attempts` attempts in total
+    pub const fn new(attempts: u8, backoff_millis: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff_millis,
+            on_crc_mismatch: true,
+            on_not_present: true,
+            on_timeout: false,
+        }
+    }
+
+    /// Returns true if the error is one this policy retries
+    pub fn is_retryable<E>(&self, err: &Ds18b20Error<E>) -> bool {
+        match err {
+            Ds18b20Error::CrcMismatch => self.on_crc_mismatch,
+            Ds18b20Error::NotPresent | Ds18b20Error::AllOnes => self.on_not_present,
+            Ds18b20Error::Timeout => self.on_timeout,
+            _ => false,
+        }
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error that is not retried, or runs out of attempts.
+    /// Returns the last result, and stores the number of retries in `retries`
+    pub(crate) fn run<T, E, D>(
+        &self,
+        retries: &Cell<u8>,
+        delay: &mut D,
+        operation: impl FnMut(&mut D) -> Ds18b20Result<T, E>,
+    ) -> Ds18b20Result<T, E>
+    where
+        D: DelayNs,
+    {
+        retries.set(0);
+        self.resume(retries, delay, operation)
+    }
+
+    /// Like `run`, but adds to the retries already in `retries`, for operations that retry several
+    /// steps separately
+    pub(crate) fn resume<T, E, D>(
+        &self,
+        retries: &Cell<u8>,
+        delay: &mut D,
+        mut operation: impl FnMut(&mut D) -> Ds18b20Result<T, E>,
+    ) -> Ds18b20Result<T, E>
+    where
+        D: DelayNs,
+    {
+        let mut attempts = 1;
+        loop {
+            match operation(delay) {
+                Err(err) if self.should_retry(&mut attempts, retries, &err) => {
+                    delay.delay_ms(self.backoff_millis);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Decides whether a failed attempt is retried, given the attempts made so far. If so, the attempt
+    /// is counted in `attempts` and `retries`, and the caller waits `backoff_millis` before retrying
+    pub(crate) fn should_retry<E>(
+        &self,
+        attempts: &mut u8,
+        retries: &Cell<u8>,
+        err: &Ds18b20Error<E>,
+    ) -> bool {
+        if *attempts >= self.attempts || !self.is_retryable(err) {
+            return false;
+        }
+        *attempts += 1;
+        retries.set(retries.get().saturating_add(1));
+        true
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::NONE
+    }
+}
@@ -0,0 +1,96 @@
+use crate::ds18s20::{self, Ds18s20};
+use crate::max31850::{self, Max31850};
+use crate::{read_scratchpad, Address, Ds18b20, Ds18b20Result, OneWireMaster, SensorFamily};
+use embedded_hal::delay::DelayNs;
+
+/// A device found by `scan`, ready to be used
+pub enum Device {
+    Ds18b20(Ds18b20),
+    Ds18s20(Ds18s20),
+    Ds1822(Ds18b20),
+    Ds1825(Ds18b20),
+    Max31850(Max31850),
+
+    /// A device from a family this crate does not support, or a DS1825 or MAX31850 whose scratchpad
+    /// could not be read to tell which one it is
+    Unknown(Address),
+}
+
+impl Device {
+    pub fn address(&self) -> Address {
+        let address = match self {
+            Device::Ds18b20(sensor) | Device::Ds1822(sensor) | Device::Ds1825(sensor) => {
+                sensor.address()
+            }
+            Device::Ds18s20(sensor) => sensor.address(),
+            Device::Max31850(converter) => converter.address(),
+            Device::Unknown(address) => return *address,
+        };
+        // scanned devices always have an address
+        *address.unwrap()
+    }
+}
+
+/// Searches the bus once, and stores every device found in `devices` (which is cleared first).
+/// The DS1825 and MAX31850 share a family code, so their scratchpad is read to tell them apart.
+/// If that read fails, the device is stored as `Device::Unknown` and the scan goes on.
+///
+/// Returns the number of devices on the bus. If it is more than the capacity of `devices`, the
+/// devices with the highest addresses were left out
+pub fn scan<B, E, const N: usize>(
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+    devices: &mut heapless::Vec<Device, N>,
+) -> Ds18b20Result<usize, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    devices.clear();
+    let mut found = 0;
+    let mut search_state = None;
+    while let Some((address, state)) = onewire.device_search(search_state.as_ref(), false, delay)? {
+        search_state = Some(state);
+        found += 1;
+        if devices.is_full() {
+            continue;
+        }
+        let device = identify(address, onewire, delay)?;
+        // cannot fail, since there is room
+        let _ = devices.push(device);
+    }
+    Ok(found)
+}
+
+/// Turns a searched address into a device handle
+pub(crate) fn identify<B, E>(
+    address: Address,
+    onewire: &mut B,
+    delay: &mut impl DelayNs,
+) -> Ds18b20Result<Device, E>
+where
+    B: OneWireMaster<Error = E>,
+{
+    let device = match address.family_code() {
+        ds18s20::FAMILY_CODE => Device::Ds18s20(Ds18s20::new(address)?),
+        family_code => match SensorFamily::from_family_code(family_code) {
+            Some(SensorFamily::Ds18b20) => {
+                Device::Ds18b20(Ds18b20::with_address(address, SensorFamily::Ds18b20))
+            }
+            Some(SensorFamily::Ds1822) => {
+                Device::Ds1822(Ds18b20::with_address(address, SensorFamily::Ds1822))
+            }
+            Some(SensorFamily::Ds1825) => {
+                match read_scratchpad(Some(&address), onewire, delay) {
+                    Ok(scratchpad) if max31850::is_max31850(&scratchpad) => {
+                        Device::Max31850(Max31850::new(address)?)
+                    }
+                    Ok(_) => Device::Ds1825(Ds18b20::with_address(address, SensorFamily::Ds1825)),
+                    // one device that cannot be read should not hide the rest of the bus
+                    Err(_) => Device::Unknown(address),
+                }
+            }
+            None => Device::Unknown(address),
+        },
+    };
+    Ok(device)
+}
@@ -0,0 +1,198 @@
+use crate::{Ds18b20Error, Ds18b20Result, Resolution, Temperature};
+use core::fmt;
+use one_wire_bus::crc::crc8;
+
+/// Temperature register after power-up (85 °C)
+const POWER_ON_TEMPERATURE: i16 = 0x0550;
+
+/// Bytes 5-7 after power-up. Genuine devices change byte 6 with every conversion, so a measured
+/// 85 °C can be told apart from the power-on value
+const POWER_ON_RESERVED_BYTES: [u8; 3] = [0xFF, 0x0C, 0x10];
+
+/// The 9 bytes returned by the Read Scratchpad command, split into their fields.
+///
+/// The fields are stored as read, without any validation, so diagnostics can look at broken devices too.
+/// Use `validate` to check the crc.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Scratchpad {
+    /// Temperature register (bytes 0-1), two's complement in 1/16 °C steps
+    pub raw_temperature: i16,
+
+    /// Alarm high threshold TH (byte 2), which is also a user byte
+    pub alarm_temp_high: i8,
+
+    /// Alarm low threshold TL (byte 3), which is also a user byte
+    pub alarm_temp_low: i8,
+
+    /// Configuration register (byte 4)
+    pub config: u8,
+
+    /// Bytes 5-7, reserved on the DS18B20 (COUNT_REMAIN and COUNT_PER_C on the DS18S20)
+    pub reserved: [u8; 3],
+
+    /// Crc of bytes 0-7 (byte 8)
+    pub crc: u8,
+}
+
+impl Scratchpad {
+    pub fn from_bytes(bytes: [u8; 9]) -> Scratchpad {
+        Scratchpad {
+            raw_temperature: i16::from_le_bytes([bytes[0], bytes[1]]),
+            alarm_temp_high: bytes[2] as i8,
+            alarm_temp_low: bytes[3] as i8,
+            config: bytes[4],
+            reserved: [bytes[5], bytes[6], bytes[7]],
+            crc: bytes[8],
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let [lsb, msb] = self.raw_temperature.to_le_bytes();
+        [
+            lsb,
+            msb,
+            self.alarm_temp_high as u8,
+            self.alarm_temp_low as u8,
+            self.config,
+            self.reserved[0],
+            self.reserved[1],
+            self.reserved[2],
+            self.crc,
+        ]
+    }
+
+    /// Returns a copy with the crc calculated from the other fields, for building scratchpads
+    pub fn with_crc(mut self) -> Scratchpad {
+        self.crc = self.expected_crc();
+        self
+    }
+
+    /// The crc that bytes 0-7 should have
+    pub fn expected_crc(&self) -> u8 {
+        crc8(&self.to_bytes()[..8])
+    }
+
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc == self.expected_crc()
+    }
+
+    /// True if every byte is 0xFF, which is what the bus reads when no device drives it
+    pub fn is_all_ones(&self) -> bool {
+        self.to_bytes() == [0xFF; 9]
+    }
+
+    /// Checks that the scratchpad was read correctly. All ones is what a missing device reads as, so it is
+    /// reported separately
+    pub fn validate<E>(&self) -> Ds18b20Result<(), E> {
+        if self.is_all_ones() {
+            return Err(Ds18b20Error::AllOnes);
+        }
+        if !self.is_crc_valid() {
+            return Err(Ds18b20Error::CrcMismatch);
+        }
+        Ok(())
+    }
+
+    /// The resolution set in the configuration register, or `None` if the register is invalid
+    pub fn resolution(&self) -> Option<Resolution> {
+        Resolution::from_config_register(self.config)
+    }
+
+    /// True if the temperature register still holds its power-on value, rather than a measured 85 °C
+    pub fn is_power_on_reset(&self) -> bool {
+        self.raw_temperature == POWER_ON_TEMPERATURE && self.reserved == POWER_ON_RESERVED_BYTES
+    }
+}
+
+impl From<[u8; 9]> for Scratchpad {
+    fn from(bytes: [u8; 9]) -> Scratchpad {
+        Scratchpad::from_bytes(bytes)
+    }
+}
+
+impl From<Scratchpad> for [u8; 9] {
+    fn from(scratchpad: Scratchpad) -> [u8; 9] {
+        scratchpad.to_bytes()
+    }
+}
+
+/// Shows every field along with how it decodes, for diagnostics
+impl fmt::Debug for Scratchpad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scratchpad {{ raw_temperature: {:#06X} ({:?}), alarm_temp_high: {}, alarm_temp_low: {}, ",
+            self.raw_temperature,
+            Temperature::from_sixteenths(self.raw_temperature),
+            self.alarm_temp_high,
+            self.alarm_temp_low
+        )?;
+        match self.resolution() {
+            Some(resolution) => write!(f, "config: {:#04X} ({:?}), ", self.config, resolution)?,
+            None => write!(f, "config: {:#04X} (invalid), ", self.config)?,
+        }
+        write!(
+            f,
+            "reserved: [{:02X}, {:02X}, {:02X}], crc: {:#04X} ({}) }}",
+            self.reserved[0],
+            self.reserved[1],
+            self.reserved[2],
+            self.crc,
+            if self.is_crc_valid() {
+                "ok"
+            } else {
+                "mismatch"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::format;
+
+    const BYTES: [u8; 9] = [0x91, 0x01, 0x4B, 0x46, 0x7F, 0xFF, 0x0F, 0x10, 0x00];
+
+    fn scratchpad() -> Scratchpad {
+        Scratchpad::from_bytes(BYTES).with_crc()
+    }
+
+    #[test]
+    fn round_trips_bytes() {
+        let scratchpad = scratchpad();
+        assert_eq!(scratchpad.raw_temperature, 0x0191);
+        assert_eq!(scratchpad.alarm_temp_high, 75);
+        assert_eq!(scratchpad.reserved, [0xFF, 0x0F, 0x10]);
+        assert_eq!(&scratchpad.to_bytes()[..8], &BYTES[..8]);
+        assert_eq!(Scratchpad::from(scratchpad.to_bytes()), scratchpad);
+    }
+
+    #[test]
+    fn validates() {
+        let mut scratchpad = scratchpad();
+        assert!(scratchpad.validate::<()>().is_ok());
+        scratchpad.config = 0x5F;
+        assert!(matches!(
+            scratchpad.validate::<()>(),
+            Err(Ds18b20Error::CrcMismatch)
+        ));
+        let all_ones = Scratchpad::from_bytes([0xFF; 9]);
+        assert!(matches!(
+            all_ones.validate::<()>(),
+            Err(Ds18b20Error::AllOnes)
+        ));
+    }
+
+    #[test]
+    fn dumps_fields() {
+        let mut scratchpad = scratchpad();
+        scratchpad.config = 0;
+        assert_eq!(
+            format!("{:?}", scratchpad),
+            "Scratchpad { raw_temperature: 0x0191 (25.0625°C), alarm_temp_high: 75, alarm_temp_low: 70, \
+             config: 0x00 (invalid), reserved: [FF, 0F, 10], crc: 0x25 (mismatch) }"
+        );
+    }
+}
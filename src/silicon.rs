@@ -0,0 +1,182 @@
+use crate::{Address, Scratchpad};
+
+/// The likely manufacturer of a device sold as a DS18B20, going by the address and scratchpad
+/// fingerprints that published surveys of clones have found
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Vendor {
+    /// A genuine Maxim (Dallas) device
+    Maxim,
+
+    /// GXCAS 18B20, which copies the Maxim address pattern but not the reserved scratchpad bytes
+    Gxcas,
+
+    /// 7Q-Tek QT18B20
+    Qt18b20,
+
+    /// UMW 18B20
+    Umw,
+
+    /// The widespread counterfeits with addresses starting `28-FF`, from an unknown manufacturer
+    Counterfeit,
+
+    /// No known fingerprint matched, the scratchpad could not tell them apart, or a device with the
+    /// Maxim fingerprints did not answer the Read Trim commands
+    Unknown,
+}
+
+/// Known problems of a vendor's devices
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// Byte 6 of the scratchpad does not change with the temperature, so a measured 85 °C reads as
+    /// `Reading::PowerOnReset`
+    pub fixed_reserved_byte: bool,
+
+    /// Conversions do not finish in parasite power mode, so the register keeps reading 85 °C
+    pub fails_in_parasite_mode: bool,
+
+    /// The accuracy is outside the datasheet limits, and drifts over time
+    pub drifts: bool,
+}
+
+impl Vendor {
+    pub fn is_genuine(self) -> bool {
+        self == Vendor::Maxim
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Vendor::Maxim | Vendor::Qt18b20 | Vendor::Unknown => Quirks::default(),
+            Vendor::Gxcas | Vendor::Umw => Quirks {
+                fixed_reserved_byte: true,
+                ..Quirks::default()
+            },
+            Vendor::Counterfeit => Quirks {
+                fixed_reserved_byte: true,
+                fails_in_parasite_mode: true,
+                drifts: true,
+            },
+        }
+    }
+
+    /// Matches the address and a scratchpad read after a conversion against the known fingerprints.
+    /// Only the address pattern and the reserved scratchpad bytes are checked:
+    /// - genuine devices have zeros in bytes 5 and 6 of the address, reserved bytes 5 and 7 of the
+    ///   scratchpad set to 0xFF and 0x10, and byte 6 set to `0x10 - (temperature LSB & 0x0F)`
+    /// - GXCAS devices have the genuine address pattern, but byte 6 stays at 0x0C
+    /// - counterfeits have 0xFF in byte 1 of the address
+    /// - QT18B20 and UMW devices have other address patterns, and byte 6 is only updated by the QT18B20
+    ///
+    /// Apart from counterfeits, nothing is matched while `is_conclusive` is false
+    pub(crate) fn from_fingerprint(address: &Address, scratchpad: &Scratchpad) -> Vendor {
+        let rom = address.0.to_le_bytes();
+        let genuine_rom = rom[5] == 0x00 && rom[6] == 0x00;
+        let [byte5, byte6, byte7] = scratchpad.reserved;
+        match (genuine_rom, tracks_temperature(scratchpad)) {
+            _ if rom[1] == 0xFF => Vendor::Counterfeit,
+            (true, Some(true)) if byte5 == 0xFF && byte7 == 0x10 => Vendor::Maxim,
+            (true, Some(false)) if byte6 == 0x0C => Vendor::Gxcas,
+            (false, Some(true)) => Vendor::Qt18b20,
+            (false, Some(false)) => Vendor::Umw,
+            _ => Vendor::Unknown,
+        }
+    }
+
+    /// Genuine devices answer the undocumented Read Trim commands (0x93 and 0x68) with their
+    /// calibration, while clones that copy the Maxim fingerprints leave the bus high. A `Maxim` match
+    /// becomes `Unknown` if neither trim byte was answered
+    pub(crate) fn check_trim(self, trim: [u8; 2]) -> Vendor {
+        match self {
+            Vendor::Maxim if trim == [0xFF, 0xFF] => Vendor::Unknown,
+            vendor => vendor,
+        }
+    }
+
+    /// False if byte 6 of the scratchpad cannot show whether it tracks the temperature, so another
+    /// conversion is needed before `from_fingerprint` can tell the vendors apart
+    pub(crate) fn is_conclusive(scratchpad: &Scratchpad) -> bool {
+        tracks_temperature(scratchpad).is_some()
+    }
+}
+
+/// Whether byte 6 holds `0x10 - (temperature LSB & 0x0F)`, or `None` if that cannot be told
+fn tracks_temperature(scratchpad: &Scratchpad) -> Option<bool> {
+    let expected_byte6 = 0x10 - (scratchpad.raw_temperature as u8 & 0x0F);
+    // a fixed 0x0C matches by chance when the temperature LSB ends in 4
+    if scratchpad.is_power_on_reset() || expected_byte6 == 0x0C {
+        None
+    } else {
+        Some(scratchpad.reserved[1] == expected_byte6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(bytes: [u8; 7]) -> Address {
+        let mut rom = [0; 8];
+        rom[..7].copy_from_slice(&bytes);
+        rom[7] = one_wire_bus::crc::crc8(&bytes);
+        Address(u64::from_le_bytes(rom))
+    }
+
+    /// 25.0625 °C, read after a conversion
+    fn scratchpad(byte6: u8) -> Scratchpad {
+        Scratchpad::from_bytes([0x91, 0x01, 75, 70, 0x7F, 0xFF, byte6, 0x10, 0]).with_crc()
+    }
+
+    #[test]
+    fn matches_fingerprints() {
+        let genuine = address([0x28, 0x5B, 0x79, 0xA2, 0x16, 0x00, 0x00]);
+        let other = address([0x28, 0x5B, 0x79, 0xA2, 0x16, 0x21, 0x3C]);
+        let counterfeit = address([0x28, 0xFF, 0x79, 0xA2, 0x16, 0x21, 0x3C]);
+        let cases = [
+            (genuine, 0x0F, Vendor::Maxim),
+            (genuine, 0x0C, Vendor::Gxcas),
+            (other, 0x0F, Vendor::Qt18b20),
+            (other, 0x0C, Vendor::Umw),
+            (counterfeit, 0x0F, Vendor::Counterfeit),
+            (genuine, 0x55, Vendor::Unknown),
+        ];
+        for &(address, byte6, vendor) in cases.iter() {
+            assert_eq!(
+                Vendor::from_fingerprint(&address, &scratchpad(byte6)),
+                vendor,
+                "{:?} with byte 6 {:#04X}",
+                address,
+                byte6
+            );
+        }
+    }
+
+    #[test]
+    fn power_on_scratchpad_is_inconclusive() {
+        let genuine = address([0x28, 0x5B, 0x79, 0xA2, 0x16, 0x00, 0x00]);
+        let power_on =
+            Scratchpad::from_bytes([0x50, 0x05, 75, 70, 0x7F, 0xFF, 0x0C, 0x10, 0]).with_crc();
+        assert!(!Vendor::is_conclusive(&power_on));
+        assert_eq!(
+            Vendor::from_fingerprint(&genuine, &power_on),
+            Vendor::Unknown
+        );
+
+        // 25.25 °C, where a fixed byte 6 matches by chance
+        let lsb_ends_in_4 =
+            Scratchpad::from_bytes([0x94, 0x01, 75, 70, 0x7F, 0xFF, 0x0C, 0x10, 0]).with_crc();
+        assert!(!Vendor::is_conclusive(&lsb_ends_in_4));
+        assert_eq!(
+            Vendor::from_fingerprint(&genuine, &lsb_ends_in_4),
+            Vendor::Unknown
+        );
+        assert!(Vendor::Counterfeit.quirks().fails_in_parasite_mode);
+        assert_eq!(Vendor::Maxim.quirks(), Quirks::default());
+    }
+
+    #[test]
+    fn unanswered_trim_reads_are_not_maxim() {
+        assert_eq!(Vendor::Maxim.check_trim([0x9D, 0xDB]), Vendor::Maxim);
+        assert_eq!(Vendor::Maxim.check_trim([0xFF, 0xDB]), Vendor::Maxim);
+        assert_eq!(Vendor::Maxim.check_trim([0xFF, 0xFF]), Vendor::Unknown);
+        assert_eq!(Vendor::Gxcas.check_trim([0x9D, 0xDB]), Vendor::Gxcas);
+    }
+}
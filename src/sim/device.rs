@@ -0,0 +1,560 @@
+use crate::{
+    commands, ds18s20, max31850, PowerMode, Resolution, Scratchpad, SensorFamily, Temperature,
+    FAMILY_CODE,
+};
+use one_wire_bus::crc::crc8;
+use one_wire_bus::{commands as rom_commands, Address};
+
+/// Scratchpad temperature register after power-up (85 °C)
+pub const POWER_ON_TEMPERATURE: i16 = 0x0550;
+
+/// Factory EEPROM contents: TH, TL and configuration (12 bits)
+const FACTORY_EEPROM: [u8; 3] = [75, 70, 0x7F];
+
+/// What the undocumented Read Trim 1 and 2 commands return. The values are arbitrary
+const FACTORY_TRIM: [u8; 2] = [0x9D, 0xDB];
+
+/// Scratchpad bytes 5-7 after power-up. Byte 6 changes with every conversion
+const RESERVED_BYTES: [u8; 3] = [0xFF, 0x0C, 0x10];
+
+/// Time for an EEPROM recall, during which read slots return 0
+const RECALL_MICROS: u64 = 100;
+
+/// What a device is doing with the bits on the bus. Devices go back to `RomCommand` on every reset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// Not selected, ignoring everything until the next reset
+    Idle,
+    RomCommand {
+        bits: u8,
+        value: u8,
+    },
+    MatchRom {
+        bit: u8,
+    },
+    ReadRom {
+        bit: u8,
+    },
+    /// Each address bit takes 3 slots: the bit, its complement, and the direction chosen by the master
+    Search {
+        bit: u8,
+        step: u8,
+    },
+    FunctionCommand {
+        bits: u8,
+        value: u8,
+    },
+    WriteScratchpad {
+        bit: u8,
+    },
+    ReadScratchpad {
+        bit: u8,
+    },
+    /// Read Trim 1 (`register` 0) or Read Trim 2 (`register` 1)
+    ReadTrim {
+        register: u8,
+        bit: u8,
+    },
+    /// Read slots report whether a conversion, EEPROM copy or recall has finished
+    Busy,
+    ReadPowerSupply,
+}
+
+/// A simulated DS18B20, or a DS18S20, DS1822 or DS1825 depending on the family code of its address.
+/// `SimDevice::max31850` simulates a MAX31850 instead, which shares the DS1825 family code
+#[derive(Clone, Debug)]
+pub struct SimDevice {
+    address: Address,
+    power_mode: PowerMode,
+    ambient: Temperature,
+    connected: bool,
+    /// Set for a MAX31850, which measures this at the tip of its thermocouple, and `ambient` at its cold junction
+    thermocouple: Option<Temperature>,
+
+    temperature_register: i16,
+    thermocouple_register: i16,
+    reserved: [u8; 3],
+    alarm_temp_high: u8,
+    alarm_temp_low: u8,
+    config: u8,
+    location_pins: u8,
+    trim: Option<[u8; 2]>,
+    eeprom: [u8; 3],
+    alarm: bool,
+
+    state: State,
+    write_buffer: [u8; 3],
+    conversion: Option<Operation>,
+    /// The copy in progress, and the values it is writing
+    eeprom_copy: Option<(Operation, [u8; 3])>,
+    recall_end_micros: Option<u64>,
+    conversion_time_percent: u8,
+    eeprom_write_millis: u16,
+}
+
+/// An operation that parasite powered devices need a strong pull-up for
+#[derive(Copy, Clone, Debug)]
+struct Operation {
+    started_micros: u64,
+    end_micros: u64,
+}
+
+impl SimDevice {
+    /// A DS18B20 with the given 48-bit serial number. The crc of the address is calculated
+    pub fn new(serial: u64) -> SimDevice {
+        SimDevice::with_family_code(FAMILY_CODE, serial)
+    }
+
+    /// A device from any family, with the given 48-bit serial number
+    pub fn with_family_code(family_code: u8, serial: u64) -> SimDevice {
+        let mut bytes = (u64::from(family_code) | (serial & 0xFFFF_FFFF_FFFF) << 8).to_le_bytes();
+        bytes[7] = crc8(&bytes[..7]);
+        SimDevice::with_address(Address(u64::from_le_bytes(bytes)))
+    }
+
+    /// A device with an exact address, which is not checked
+    pub fn with_address(address: Address) -> SimDevice {
+        let mut device = SimDevice {
+            address,
+            power_mode: PowerMode::External,
+            ambient: Temperature::from_degrees(20),
+            connected: true,
+            thermocouple: None,
+            temperature_register: POWER_ON_TEMPERATURE,
+            thermocouple_register: 0,
+            reserved: RESERVED_BYTES,
+            alarm_temp_high: 0,
+            alarm_temp_low: 0,
+            config: 0,
+            location_pins: 0,
+            trim: Some(FACTORY_TRIM),
+            eeprom: FACTORY_EEPROM,
+            alarm: false,
+            state: State::Idle,
+            write_buffer: [0; 3],
+            conversion: None,
+            eeprom_copy: None,
+            recall_end_micros: None,
+            conversion_time_percent: 80,
+            eeprom_write_millis: 10,
+        };
+        device.power_cycle();
+        device
+    }
+
+    /// A MAX31850 thermocouple converter with the given 48-bit serial number, and a thermocouple at 20 °C
+    pub fn max31850(serial: u64) -> SimDevice {
+        let mut device = SimDevice::with_family_code(max31850::FAMILY_CODE, serial);
+        device.thermocouple = Some(Temperature::from_degrees(20));
+        device.power_cycle();
+        device
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn power_mode(&self) -> PowerMode {
+        self.power_mode
+    }
+
+    pub fn set_power_mode(&mut self, power_mode: PowerMode) {
+        self.power_mode = power_mode;
+    }
+
+    /// The temperature the next conversion will measure
+    pub fn ambient(&self) -> Temperature {
+        self.ambient
+    }
+
+    pub fn set_ambient(&mut self, ambient: Temperature) {
+        self.ambient = ambient;
+    }
+
+    /// Sets the temperature the next conversion of a MAX31850 will measure at the tip of its thermocouple.
+    /// Does nothing on other devices
+    pub fn set_thermocouple(&mut self, thermocouple: Temperature) {
+        if self.thermocouple.is_some() {
+            self.thermocouple = Some(thermocouple);
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Disconnected devices do not respond to anything, but keep their state
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
+    /// How long conversions take, as a percentage of the datasheet maximum. Real devices take about 80%
+    pub fn set_conversion_time_percent(&mut self, percent: u8) {
+        self.conversion_time_percent = percent;
+    }
+
+    /// How long copying the scratchpad to EEPROM takes. The datasheet maximum is 10ms
+    pub fn set_eeprom_write_millis(&mut self, millis: u16) {
+        self.eeprom_write_millis = millis;
+    }
+
+    /// The (TH, TL, configuration) bytes stored in EEPROM
+    pub fn eeprom(&self) -> [u8; 3] {
+        self.eeprom
+    }
+
+    /// Wires the address pins (AD0-AD3) of a DS1825 or MAX31850. Only the low 4 bits are used
+    pub fn set_location_pins(&mut self, location: u8) {
+        self.location_pins = location & 0x0F;
+    }
+
+    /// Sets the bytes the undocumented Read Trim 1 and 2 commands return, or `None` for a clone that
+    /// ignores those commands
+    pub fn set_trim(&mut self, trim: Option<[u8; 2]>) {
+        self.trim = trim;
+    }
+
+    /// True if the last conversion was outside of the alarm thresholds
+    pub fn is_alarming(&self) -> bool {
+        self.alarm
+    }
+
+    /// The resolution conversions are done at. A DS18S20 always converts for as long as 12 bits take
+    pub fn resolution(&self) -> Resolution {
+        // only bits 5 and 6 can be written, so this is always valid
+        Resolution::from_config_register(self.config).unwrap()
+    }
+
+    /// Returns to the power-on state: the temperature register reads 85 °C, and the configuration
+    /// is recalled from EEPROM. Any operation in progress is lost
+    pub fn power_cycle(&mut self) {
+        self.temperature_register = match self.thermocouple {
+            Some(_) => 0,
+            None if self.is_ds18s20() => ds18s20::POWER_ON_TEMPERATURE,
+            None => POWER_ON_TEMPERATURE,
+        };
+        self.thermocouple_register = 0;
+        self.reserved = RESERVED_BYTES;
+        self.alarm_temp_high = self.eeprom[0];
+        self.alarm_temp_low = self.eeprom[1];
+        self.config = self.eeprom[2];
+        self.alarm = false;
+        self.state = State::Idle;
+        self.conversion = None;
+        self.eeprom_copy = None;
+        self.recall_end_micros = None;
+    }
+
+    /// The scratchpad that Read Scratchpad returns, including the crc
+    pub fn scratchpad(&self) -> Scratchpad {
+        if self.thermocouple.is_some() {
+            // thermocouple and cold junction temperatures, with their fault bits clear
+            let [tc_lsb, tc_msb] = self.thermocouple_register.to_le_bytes();
+            let [cj_lsb, cj_msb] = (self.temperature_register << 4).to_le_bytes();
+            let config = 0xF0 | self.location_pins;
+            let bytes = [tc_lsb, tc_msb, cj_lsb, cj_msb, config, 0xFF, 0xFF, 0xFF, 0];
+            return Scratchpad::from_bytes(bytes).with_crc();
+        }
+        Scratchpad {
+            raw_temperature: self.temperature_register,
+            alarm_temp_high: self.alarm_temp_high as i8,
+            alarm_temp_low: self.alarm_temp_low as i8,
+            config: self.config_register(),
+            reserved: self.reserved,
+            crc: 0,
+        }
+        .with_crc()
+    }
+
+    /// The configuration register as it is read. On a DS1825 the low 4 bits are the address pins,
+    /// and a DS18S20 has a reserved byte instead
+    fn config_register(&self) -> u8 {
+        if self.is_ds18s20() {
+            return 0xFF;
+        }
+        match SensorFamily::from_family_code(self.address.family_code()) {
+            Some(SensorFamily::Ds1825) => (self.config & 0x60) | 0x10 | self.location_pins,
+            _ => self.config,
+        }
+    }
+
+    fn is_ds18s20(&self) -> bool {
+        self.address.family_code() == ds18s20::FAMILY_CODE
+    }
+
+    fn conversion_micros(&self) -> u64 {
+        let max_millis = match self.thermocouple {
+            Some(_) => u64::from(max31850::MAX_MEASUREMENT_TIME_MILLIS),
+            None => u64::from(self.resolution().max_measurement_time_millis()),
+        };
+        max_millis * 10 * u64::from(self.conversion_time_percent)
+    }
+
+    /// Finishes any operation that has had enough time. `pullup` is the period the strong pull-up was
+    /// last enabled for, which parasite powered devices need to cover the whole operation
+    pub(super) fn update(&mut self, now_micros: u64, pullup: (Option<u64>, Option<u64>)) {
+        let powered = |operation: &Operation, power_mode: PowerMode| match power_mode {
+            PowerMode::External => true,
+            PowerMode::Parasite => match pullup {
+                (Some(enabled), disabled) => {
+                    enabled <= operation.started_micros
+                        && disabled.is_none_or(|disabled| disabled >= operation.end_micros)
+                }
+                (None, _) => false,
+            },
+        };
+        if let Some(conversion) = self.conversion {
+            if now_micros >= conversion.end_micros {
+                self.conversion = None;
+                if powered(&conversion, self.power_mode) {
+                    self.finish_conversion();
+                }
+            }
+        }
+        if let Some((copy, values)) = self.eeprom_copy {
+            if now_micros >= copy.end_micros {
+                self.eeprom_copy = None;
+                if powered(&copy, self.power_mode) {
+                    self.eeprom = values;
+                }
+            }
+        }
+        if let Some(end) = self.recall_end_micros {
+            if now_micros >= end {
+                self.recall_end_micros = None;
+                self.alarm_temp_high = self.eeprom[0];
+                self.alarm_temp_low = self.eeprom[1];
+                self.config = self.eeprom[2];
+            }
+        }
+    }
+
+    fn finish_conversion(&mut self) {
+        if let Some(thermocouple) = self.thermocouple {
+            // 0.25 °C steps, and the cold junction in 1/16 °C steps
+            self.thermocouple_register = thermocouple.as_sixteenths() & !0b11;
+            self.temperature_register = self.ambient.as_sixteenths();
+            return;
+        }
+        if self.is_ds18s20() {
+            self.finish_ds18s20_conversion();
+            return;
+        }
+        let undefined_bits = match self.resolution() {
+            Resolution::Bits12 => 0b000,
+            Resolution::Bits11 => 0b001,
+            Resolution::Bits10 => 0b011,
+            Resolution::Bits9 => 0b111,
+        };
+        self.temperature_register = self.ambient.as_sixteenths() & !undefined_bits;
+        self.reserved[1] = 0x10 - (self.temperature_register as u8 & 0x0F);
+        // the thresholds are compared with the whole degrees of the register
+        let whole_degrees = (self.temperature_register >> 4) as i8;
+        self.alarm = whole_degrees >= self.alarm_temp_high as i8
+            || whole_degrees <= self.alarm_temp_low as i8;
+    }
+
+    /// The register holds 0.5 °C steps, and COUNT_REMAIN is chosen so that
+    /// TEMP_READ - 0.25 + (16 - COUNT_REMAIN) / 16 gives the ambient temperature back
+    fn finish_ds18s20_conversion(&mut self) {
+        let ambient = i32::from(self.ambient.as_sixteenths());
+        self.temperature_register = ((ambient + 4) >> 3) as i16;
+        let temp_read = i32::from(self.temperature_register & !1) * 8;
+        self.reserved[1] = (temp_read + 12 - ambient) as u8;
+        let whole_degrees = (self.temperature_register >> 1) as i8;
+        self.alarm = whole_degrees >= self.alarm_temp_high as i8
+            || whole_degrees <= self.alarm_temp_low as i8;
+    }
+
+    fn is_busy(&self) -> bool {
+        self.conversion.is_some() || self.eeprom_copy.is_some() || self.recall_end_micros.is_some()
+    }
+
+    /// Returns true if the device answers the reset with a presence pulse
+    pub(super) fn reset(&mut self) -> bool {
+        if !self.connected {
+            return false;
+        }
+        self.state = State::RomCommand { bits: 0, value: 0 };
+        true
+    }
+
+    /// A slot where the master writes a bit (a read slot looks like writing 1 to devices that are listening)
+    pub(super) fn write_slot(&mut self, value: bool, now_micros: u64) {
+        if !self.connected {
+            return;
+        }
+        self.state = match self.state {
+            State::RomCommand { bits, value: byte } => {
+                let byte = byte | (value as u8) << bits;
+                if bits < 7 {
+                    State::RomCommand {
+                        bits: bits + 1,
+                        value: byte,
+                    }
+                } else {
+                    self.rom_command(byte)
+                }
+            }
+            State::MatchRom { bit } => {
+                if value != self.address_bit(bit) {
+                    State::Idle
+                } else if bit == 63 {
+                    State::FunctionCommand { bits: 0, value: 0 }
+                } else {
+                    State::MatchRom { bit: bit + 1 }
+                }
+            }
+            State::Search { bit, step: 2 } => {
+                if value != self.address_bit(bit) {
+                    State::Idle
+                } else if bit == 63 {
+                    State::FunctionCommand { bits: 0, value: 0 }
+                } else {
+                    State::Search {
+                        bit: bit + 1,
+                        step: 0,
+                    }
+                }
+            }
+            State::FunctionCommand { bits, value: byte } => {
+                let byte = byte | (value as u8) << bits;
+                if bits < 7 {
+                    State::FunctionCommand {
+                        bits: bits + 1,
+                        value: byte,
+                    }
+                } else {
+                    self.function_command(byte, now_micros)
+                }
+            }
+            State::WriteScratchpad { bit } => {
+                let byte = usize::from(bit / 8);
+                if value {
+                    self.write_buffer[byte] |= 1 << (bit % 8);
+                }
+                if bit == 15 && self.is_ds18s20() {
+                    // there is no configuration register to write
+                    self.alarm_temp_high = self.write_buffer[0];
+                    self.alarm_temp_low = self.write_buffer[1];
+                    State::Idle
+                } else if bit == 23 {
+                    self.alarm_temp_high = self.write_buffer[0];
+                    self.alarm_temp_low = self.write_buffer[1];
+                    // only the resolution bits can be written
+                    self.config = (self.write_buffer[2] & 0x60) | 0x1F;
+                    State::Idle
+                } else {
+                    State::WriteScratchpad { bit: bit + 1 }
+                }
+            }
+            state => state,
+        };
+    }
+
+    /// A read slot. Returns false if the device holds the bus low
+    pub(super) fn read_slot(&mut self, now_micros: u64) -> bool {
+        if !self.connected {
+            return true;
+        }
+        match self.state {
+            State::ReadRom { bit } => {
+                self.state = if bit == 63 {
+                    State::FunctionCommand { bits: 0, value: 0 }
+                } else {
+                    State::ReadRom { bit: bit + 1 }
+                };
+                self.address_bit(bit)
+            }
+            State::Search { bit, step } if step < 2 => {
+                self.state = State::Search {
+                    bit,
+                    step: step + 1,
+                };
+                // the bit, then its complement
+                self.address_bit(bit) != (step == 1)
+            }
+            State::ReadScratchpad { bit } => {
+                if bit >= 72 {
+                    return true;
+                }
+                self.state = State::ReadScratchpad { bit: bit + 1 };
+                self.scratchpad().to_bytes()[usize::from(bit / 8)] & (1 << (bit % 8)) != 0
+            }
+            State::ReadTrim { register, bit } => {
+                let trim = match self.trim {
+                    Some(trim) if bit < 8 => trim[usize::from(register)],
+                    _ => return true,
+                };
+                self.state = State::ReadTrim {
+                    register,
+                    bit: bit + 1,
+                };
+                trim & (1 << bit) != 0
+            }
+            // parasite powered devices cannot drive the bus while busy, so the pull-up makes it read as done
+            State::Busy => self.power_mode == PowerMode::Parasite || !self.is_busy(),
+            State::ReadPowerSupply => self.power_mode == PowerMode::External,
+            _ => {
+                // listening devices see a read slot as a 1 being written
+                self.write_slot(true, now_micros);
+                true
+            }
+        }
+    }
+
+    fn address_bit(&self, bit: u8) -> bool {
+        self.address.0 & (1 << bit) != 0
+    }
+
+    fn rom_command(&mut self, command: u8) -> State {
+        match command {
+            rom_commands::MATCH_ROM => State::MatchRom { bit: 0 },
+            rom_commands::SKIP_ROM => State::FunctionCommand { bits: 0, value: 0 },
+            commands::READ_ROM => State::ReadRom { bit: 0 },
+            rom_commands::SEARCH_NORMAL => State::Search { bit: 0, step: 0 },
+            commands::ALARM_SEARCH if self.alarm => State::Search { bit: 0, step: 0 },
+            _ => State::Idle,
+        }
+    }
+
+    fn function_command(&mut self, command: u8, now_micros: u64) -> State {
+        match command {
+            commands::CONVERT_TEMP => {
+                self.conversion = Some(Operation {
+                    started_micros: now_micros,
+                    end_micros: now_micros + self.conversion_micros(),
+                });
+                State::Busy
+            }
+            commands::WRITE_SCRATCHPAD => {
+                self.write_buffer = [0; 3];
+                State::WriteScratchpad { bit: 0 }
+            }
+            commands::READ_SCRATCHPAD => State::ReadScratchpad { bit: 0 },
+            commands::COPY_SCRATCHPAD => {
+                let operation = Operation {
+                    started_micros: now_micros,
+                    end_micros: now_micros + u64::from(self.eeprom_write_millis) * 1000,
+                };
+                let values = [self.alarm_temp_high, self.alarm_temp_low, self.config];
+                self.eeprom_copy = Some((operation, values));
+                State::Busy
+            }
+            commands::RECALL_EEPROM => {
+                self.recall_end_micros = Some(now_micros + RECALL_MICROS);
+                State::Busy
+            }
+            commands::READ_POWER_SUPPLY => State::ReadPowerSupply,
+            commands::READ_TRIM_1 => State::ReadTrim {
+                register: 0,
+                bit: 0,
+            },
+            commands::READ_TRIM_2 => State::ReadTrim {
+                register: 1,
+                bit: 0,
+            },
+            _ => State::Idle,
+        }
+    }
+}
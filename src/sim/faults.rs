@@ -0,0 +1,187 @@
+//! Faults seen in the field (noise, loose wires, shorts, brown-outs), injected into a `SimBus` at
+//! deterministic points so tests can assert exactly how the driver reacts.
+
+use super::SimBus;
+use crate::bus::OneWireMaster;
+use core::convert::Infallible;
+use embedded_hal::delay::DelayNs;
+use one_wire_bus::{Address, OneWireError, OneWireResult};
+use std::vec::Vec;
+
+/// Something that goes wrong with the bus or a device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The device stops responding, as if its wire came loose. Reads from it return all 1s
+    Disconnect(Address),
+    Reconnect(Address),
+    /// The device loses power and restarts, so its temperature register reads 85 °C again and its
+    /// configuration is recalled from EEPROM
+    BrownOut(Address),
+    /// The next read slot returns the wrong value
+    FlipReadBit,
+    /// The bus is shorted to ground. Resets fail with `BusNotHigh`, reads return 0 and writes are lost
+    StuckLow,
+    /// The short is removed
+    Released,
+}
+
+/// Wraps a `SimBus`, injecting faults that were scheduled up front, and random bit flips from a seeded generator
+pub struct FaultyBus {
+    bus: SimBus,
+    rng: XorShift,
+    bit_flips_per_million: u32,
+    /// Faults and the slot they happen at
+    scheduled: Vec<(u64, Fault)>,
+    slots: u64,
+    flip_next_read: bool,
+    stuck_low: bool,
+    flipped_bits: u32,
+}
+
+impl FaultyBus {
+    /// The same seed and schedule always inject the same faults at the same points
+    pub fn new(bus: SimBus, seed: u64) -> FaultyBus {
+        FaultyBus {
+            bus,
+            rng: XorShift::new(seed),
+            bit_flips_per_million: 0,
+            scheduled: Vec::new(),
+            slots: 0,
+            flip_next_read: false,
+            stuck_low: false,
+            flipped_bits: 0,
+        }
+    }
+
+    pub fn inner(&self) -> &SimBus {
+        &self.bus
+    }
+
+    pub fn inner_mut(&mut self) -> &mut SimBus {
+        &mut self.bus
+    }
+
+    pub fn into_inner(self) -> SimBus {
+        self.bus
+    }
+
+    /// Randomly flips read slots, at the given rate
+    pub fn set_bit_flips_per_million(&mut self, rate: u32) {
+        self.bit_flips_per_million = rate;
+    }
+
+    /// Injects a fault right before the given number of bus slots (resets, reads and writes) from now.
+    /// A delay of 0 injects it before the next slot
+    pub fn schedule(&mut self, slots_from_now: u64, fault: Fault) {
+        self.scheduled.push((self.slots + slots_from_now, fault));
+    }
+
+    /// Injects a fault now
+    pub fn inject(&mut self, fault: Fault) {
+        match fault {
+            Fault::Disconnect(address) => self.set_connected(&address, false),
+            Fault::Reconnect(address) => self.set_connected(&address, true),
+            Fault::BrownOut(address) => {
+                if let Some(device) = self.bus.device_mut(&address) {
+                    device.power_cycle();
+                }
+            }
+            Fault::FlipReadBit => self.flip_next_read = true,
+            Fault::StuckLow => self.stuck_low = true,
+            Fault::Released => self.stuck_low = false,
+        }
+    }
+
+    pub fn is_stuck_low(&self) -> bool {
+        self.stuck_low
+    }
+
+    /// The number of read slots that returned the wrong value so far
+    pub fn flipped_bits(&self) -> u32 {
+        self.flipped_bits
+    }
+
+    fn set_connected(&mut self, address: &Address, connected: bool) {
+        if let Some(device) = self.bus.device_mut(address) {
+            device.set_connected(connected);
+        }
+    }
+
+    /// Injects the faults that are due before the next slot
+    fn start_slot(&mut self) {
+        let slots = self.slots;
+        let mut due = Vec::new();
+        self.scheduled.retain(|&(at, fault)| {
+            if at <= slots {
+                due.push(fault);
+            }
+            at > slots
+        });
+        for fault in due {
+            self.inject(fault);
+        }
+        self.slots += 1;
+    }
+
+    fn should_flip(&mut self) -> bool {
+        if self.flip_next_read {
+            self.flip_next_read = false;
+            return true;
+        }
+        self.bit_flips_per_million > 0
+            && self.rng.next() % 1_000_000 < u64::from(self.bit_flips_per_million)
+    }
+}
+
+impl OneWireMaster for FaultyBus {
+    type Error = Infallible;
+
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        self.start_slot();
+        if self.stuck_low {
+            return Err(OneWireError::BusNotHigh);
+        }
+        self.bus.reset(delay)
+    }
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        self.start_slot();
+        // the slot still happens, so the devices stay in step with the master
+        let value = self.bus.read_bit(delay)? && !self.stuck_low;
+        if self.should_flip() {
+            self.flipped_bits += 1;
+            return Ok(!value);
+        }
+        Ok(value)
+    }
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Infallible> {
+        self.start_slot();
+        self.bus.write_bit(value && !self.stuck_low, delay)
+    }
+}
+
+/// xorshift64*, which is plenty for picking bits to flip, and needs no dependencies
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // the state must never be 0
+        XorShift(if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
@@ -0,0 +1,238 @@
+//! A simulated 1-wire bus with DS18B20s on it, so the driver can be tested without hardware.
+//!
+//! The devices are simulated bit by bit (ROM commands and search, scratchpad, EEPROM, conversion timing,
+//! parasite power and alarm flags), behind the same `OneWireMaster` trait as the real bus masters.
+//! Time only passes when the bus is used, or through the `SimDelay`, `SimClock` and `SimStrongPullup`
+//! handles, which share the bus' clock.
+//!
+//! `FaultyBus` wraps a `SimBus` to inject faults (bit flips, disconnects, brown-outs, a shorted bus).
+//!
+//! ```ignore
+//! let mut bus = SimBus::new();
+//! bus.add_device(SimDevice::new(0x0316a2795b));
+//! let mut delay = bus.delay();
+//! ```
+
+mod device;
+mod faults;
+
+pub use device::{SimDevice, POWER_ON_TEMPERATURE};
+pub use faults::{Fault, FaultyBus};
+
+use crate::bus::OneWireMaster;
+use crate::measurement::{Clock, Instant};
+use crate::StrongPullup;
+use core::convert::Infallible;
+use embedded_hal::delay::DelayNs;
+use one_wire_bus::{Address, OneWireResult};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::vec::Vec;
+
+const RESET_MICROS: u64 = 960;
+const SLOT_MICROS: u64 = 70;
+
+#[derive(Default, Debug)]
+struct Shared {
+    now_nanos: Cell<u64>,
+    /// When the strong pull-up was last enabled, and disabled again
+    pullup: Cell<(Option<u64>, Option<u64>)>,
+}
+
+impl Shared {
+    fn now_micros(&self) -> u64 {
+        self.now_nanos.get() / 1000
+    }
+
+    fn advance_nanos(&self, nanos: u64) {
+        self.now_nanos.set(self.now_nanos.get() + nanos);
+    }
+}
+
+pub struct SimBus {
+    devices: Vec<SimDevice>,
+    shared: Rc<Shared>,
+    stats: RefCell<BusStats>,
+}
+
+/// Counts of the bus operations so far, for asserting on how the driver uses the bus
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusStats {
+    pub resets: u32,
+    pub read_slots: u32,
+    pub write_slots: u32,
+}
+
+impl SimBus {
+    pub fn new() -> SimBus {
+        SimBus {
+            devices: Vec::new(),
+            shared: Rc::new(Shared::default()),
+            stats: RefCell::new(BusStats::default()),
+        }
+    }
+
+    pub fn add_device(&mut self, device: SimDevice) {
+        self.devices.push(device);
+    }
+
+    /// The devices on the bus, with any operation that is due by now finished
+    pub fn simulated_devices(&mut self) -> &[SimDevice] {
+        self.start_slot(0);
+        &self.devices
+    }
+
+    pub fn device_mut(&mut self, address: &Address) -> Option<&mut SimDevice> {
+        self.start_slot(0);
+        self.devices
+            .iter_mut()
+            .find(|device| device.address() == *address)
+    }
+
+    /// A delay that advances the bus' clock instead of sleeping
+    pub fn delay(&self) -> SimDelay {
+        SimDelay {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// A monotonic clock that reads the bus' clock, for non-blocking measurements
+    pub fn clock(&self) -> SimClock {
+        SimClock {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// A strong pull-up that parasite powered devices need to be enabled during conversions and EEPROM writes
+    pub fn strong_pullup(&self) -> SimStrongPullup {
+        SimStrongPullup {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Microseconds since the bus was created
+    pub fn now_micros(&self) -> u64 {
+        self.shared.now_micros()
+    }
+
+    pub fn stats(&self) -> BusStats {
+        *self.stats.borrow()
+    }
+
+    /// Advances the clock by one slot, and lets devices finish anything that is due
+    fn start_slot(&mut self, micros: u64) -> u64 {
+        self.shared.advance_nanos(micros * 1000);
+        let now = self.shared.now_micros();
+        let pullup = self.shared.pullup.get();
+        for device in self.devices.iter_mut() {
+            device.update(now, pullup);
+        }
+        now
+    }
+}
+
+impl Default for SimBus {
+    fn default() -> SimBus {
+        SimBus::new()
+    }
+}
+
+impl OneWireMaster for SimBus {
+    type Error = Infallible;
+
+    fn reset(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        self.stats.borrow_mut().resets += 1;
+        self.start_slot(RESET_MICROS);
+        let mut present = false;
+        for device in self.devices.iter_mut() {
+            present |= device.reset();
+        }
+        Ok(present)
+    }
+
+    fn read_bit(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        self.stats.borrow_mut().read_slots += 1;
+        let now = self.start_slot(SLOT_MICROS);
+        // any device can pull the bus low
+        let mut value = true;
+        for device in self.devices.iter_mut() {
+            value &= device.read_slot(now);
+        }
+        Ok(value)
+    }
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        _delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Infallible> {
+        self.stats.borrow_mut().write_slots += 1;
+        let now = self.start_slot(SLOT_MICROS);
+        for device in self.devices.iter_mut() {
+            device.write_slot(value, now);
+        }
+        Ok(())
+    }
+}
+
+/// Advances the simulated clock instead of sleeping
+#[derive(Clone)]
+pub struct SimDelay {
+    shared: Rc<Shared>,
+}
+
+impl DelayNs for SimDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.shared.advance_nanos(u64::from(ns));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for SimDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.shared.advance_nanos(u64::from(ns));
+    }
+}
+
+/// Reads the simulated clock
+#[derive(Clone)]
+pub struct SimClock {
+    shared: Rc<Shared>,
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Instant {
+        Instant((self.shared.now_micros() / 1000) as u32)
+    }
+}
+
+/// Records when it is enabled and disabled, so parasite powered devices can tell whether they had enough power
+#[derive(Clone)]
+pub struct SimStrongPullup {
+    shared: Rc<Shared>,
+}
+
+impl SimStrongPullup {
+    pub fn is_enabled(&self) -> bool {
+        matches!(self.shared.pullup.get(), (Some(_), None))
+    }
+}
+
+impl StrongPullup for SimStrongPullup {
+    type Error = Infallible;
+
+    fn enable(&mut self) -> Result<(), Infallible> {
+        self.shared
+            .pullup
+            .set((Some(self.shared.now_micros()), None));
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), Infallible> {
+        let (enabled, _) = self.shared.pullup.get();
+        self.shared
+            .pullup
+            .set((enabled, Some(self.shared.now_micros())));
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use core::convert::Infallible;
+use embedded_hal::digital;
+
+/// Controls a strong pull-up (usually a MOSFET between the data line and VDD), which parasite
+/// powered devices need to supply enough current during conversions and EEPROM writes.
+///
+/// The driver enables it directly after sending the command (the datasheet requires this within 10µs,
+/// so `enable` should not block) and disables it once the operation has had time to finish.
+/// Its errors are independent of the bus master's, and are reported as `Ds18b20Error::StrongPullup`.
+pub trait StrongPullup {
+    type Error: digital::Error;
+
+    fn enable(&mut self) -> Result<(), Self::Error>;
+    fn disable(&mut self) -> Result<(), Self::Error>;
+}
+
+/// For buses where every device is externally powered, or that rely on the regular pull-up resistor
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoStrongPullup;
+
+impl StrongPullup for NoStrongPullup {
+    type Error = Infallible;
+
+    fn enable(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
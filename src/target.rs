@@ -0,0 +1,338 @@
+use crate::measurement::PullupHold;
+use crate::{
+    commands, hold_strong_pullup, pullup_error, read_power_supply, read_scratchpad,
+    recall_from_eeprom, send_command, send_powered_command, verify_eeprom, wait_for_high_read_slot,
+    Address, Ds18b20Result, OneWireMaster, PowerMode, RetryPolicy, Scratchpad, StrongPullup,
+    EEPROM_WRITE_MILLIS,
+};
+use core::cell::Cell;
+use embedded_hal::delay::DelayNs;
+
+/// What every device type needs to run commands on its device: how it is addressed and powered,
+/// and how failed operations are retried. The device types only add decoding on top, and
+/// dereference to this for the settings they share
+pub struct Target {
+    /// `None` for a device that is alone on its bus, which is addressed with Skip ROM
+    pub(crate) address: Option<Address>,
+    pub(crate) power_mode: PowerMode,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) last_retries: Cell<u8>,
+}
+
+impl Target {
+    pub(crate) fn new(address: Option<Address>) -> Target {
+        Target {
+            address,
+            power_mode: PowerMode::assumed(),
+            retry_policy: RetryPolicy::NONE,
+            last_retries: Cell::new(0),
+        }
+    }
+
+    /// Returns the device address, or `None` if it was created with `single_on_bus`
+    pub fn address(&self) -> Option<&Address> {
+        self.address.as_ref()
+    }
+
+    /// Returns the power mode used for conversions and EEPROM writes.
+    /// This is `PowerMode::Parasite` until it is detected or set
+    pub fn power_mode(&self) -> PowerMode {
+        self.power_mode
+    }
+
+    /// Overrides the power mode, for when it is known up front and detection can be skipped
+    pub fn set_power_mode(&mut self, power_mode: PowerMode) {
+        self.power_mode = power_mode;
+    }
+
+    /// Returns the policy for retrying reads, conversions and configuration writes that fail.
+    /// Nothing is retried until `set_retry_policy` is called
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Returns the number of times the last operation was retried
+    pub fn last_retries(&self) -> u8 {
+        self.last_retries.get()
+    }
+
+    /// Asks the device how it is powered, and remembers the result for later operations
+    pub fn detect_power_mode<B, E>(
+        &mut self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<PowerMode, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let power_mode = self.read_power_mode(onewire, delay)?;
+        self.power_mode = power_mode;
+        Ok(power_mode)
+    }
+
+    /// Returns true if the device is powered from the data line (using the Read Power Supply command).
+    /// This does not change the stored power mode, use `detect_power_mode` for that
+    pub fn is_parasite_powered<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<bool, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let power_mode = self.read_power_mode(onewire, delay)?;
+        Ok(power_mode == PowerMode::Parasite)
+    }
+
+    /// Runs an operation with the retry policy, and records how many retries it needed
+    pub(crate) fn retry<T, E, D>(
+        &self,
+        delay: &mut D,
+        operation: impl FnMut(&mut D) -> Ds18b20Result<T, E>,
+    ) -> Ds18b20Result<T, E>
+    where
+        D: DelayNs,
+    {
+        self.retry_policy.run(&self.last_retries, delay, operation)
+    }
+
+    /// Asks the device how it is powered, without remembering the result
+    pub(crate) fn read_power_mode<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<PowerMode, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.retry(delay, |delay| {
+            read_power_supply(self.address(), onewire, delay)
+        })
+    }
+
+    /// Starts a conversion, and in parasite power mode holds the strong pull-up for `millis`.
+    /// `started` is called as soon as the command has been sent, and its result is returned
+    pub(crate) fn start_conversion<B, E, T>(
+        &self,
+        millis: u16,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+        started: impl FnOnce() -> T,
+    ) -> Ds18b20Result<T, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let master_pullup = self.retry(delay, |delay| {
+            send_powered_command(
+                commands::CONVERT_TEMP,
+                self.address(),
+                self.power_mode,
+                onewire,
+                delay,
+            )
+        })?;
+        let started = started();
+        if self.power_mode == PowerMode::Parasite {
+            hold_strong_pullup(master_pullup, onewire, pullup, millis, delay)?;
+        }
+        Ok(started)
+    }
+
+    /// Sends Convert T and, in parasite power mode, turns on a strong pull-up that is left on for the
+    /// caller to release. Failed attempts are retried straight away, so this only waits for the bus itself
+    pub(crate) fn begin_conversion<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<PullupHold, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let retry_policy = RetryPolicy {
+            backoff_millis: 0,
+            ..self.retry_policy
+        };
+        let master_pullup = retry_policy.run(&self.last_retries, delay, |delay| {
+            send_powered_command(
+                commands::CONVERT_TEMP,
+                self.address(),
+                self.power_mode,
+                onewire,
+                delay,
+            )
+        })?;
+        self.hold_pullup(master_pullup, pullup)
+    }
+
+    /// Turns on the strong pull-up in parasite power mode, right after a command sent with
+    /// `send_powered_command`. `master_pullup` is what that returned
+    fn hold_pullup<E>(
+        &self,
+        master_pullup: bool,
+        pullup: &mut impl StrongPullup,
+    ) -> Ds18b20Result<PullupHold, E> {
+        match self.power_mode {
+            PowerMode::External => Ok(PullupHold::None),
+            PowerMode::Parasite if master_pullup => Ok(PullupHold::Master),
+            PowerMode::Parasite => {
+                pullup.enable().map_err(pullup_error)?;
+                Ok(PullupHold::External)
+            }
+        }
+    }
+
+    /// Waits for a conversion started with `begin_conversion` to finish. In parasite power mode that
+    /// means waiting `millis`, then turning off the strong pull-up
+    pub(crate) fn finish_conversion<B, E>(
+        &self,
+        millis: u16,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        match self.power_mode {
+            PowerMode::Parasite => {
+                delay.delay_ms(u32::from(millis));
+                // whichever of them was turned on
+                onewire.disarm_strong_pullup(delay)?;
+                pullup.disable().map_err(pullup_error)
+            }
+            PowerMode::External => self.wait_for_conversion(millis, onewire, delay),
+        }
+    }
+
+    /// Polls the bus until a conversion is finished, for up to `timeout_millis`.
+    /// Returns immediately in parasite power mode, where the conversion was waited for when it was started
+    pub(crate) fn wait_for_conversion<B, E>(
+        &self,
+        timeout_millis: u16,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        match self.power_mode {
+            PowerMode::Parasite => Ok(()),
+            PowerMode::External => self.retry(delay, |delay| {
+                wait_for_high_read_slot(onewire, delay, timeout_millis)
+            }),
+        }
+    }
+
+    pub(crate) fn read_scratchpad<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<Scratchpad, E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.retry(delay, |delay| {
+            read_scratchpad(self.address(), onewire, delay)
+        })
+    }
+
+    /// Writes the bytes that follow the temperature: TH, TL, and the configuration register of the
+    /// families that have one
+    pub(crate) fn write_scratchpad<B, E>(
+        &self,
+        bytes: &[u8],
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.retry(delay, |delay| {
+            send_command(commands::WRITE_SCRATCHPAD, self.address(), onewire, delay)?;
+            for &byte in bytes {
+                onewire.write_byte(byte, delay)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Copies the scratchpad to EEPROM, then recalls it and checks that it holds the same configuration
+    pub(crate) fn save_to_eeprom<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let (saved, hold) = self.begin_eeprom_copy(onewire, pullup, delay)?;
+        delay.delay_ms(u32::from(EEPROM_WRITE_MILLIS));
+        self.end_eeprom_copy(&saved, hold, onewire, pullup, delay)
+    }
+
+    /// Reads the scratchpad that is about to be saved, then starts copying it to EEPROM, with the
+    /// strong pull-up on in parasite power mode. The copy takes `EEPROM_WRITE_MILLIS`.
+    /// Only the read is retried: after a failed copy, a retry would read back the old values that the
+    /// recall loaded, and save those instead
+    pub(crate) fn begin_eeprom_copy<B, E>(
+        &self,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(Scratchpad, PullupHold), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        let saved = self.read_scratchpad(onewire, delay)?;
+        let master_pullup = send_powered_command(
+            commands::COPY_SCRATCHPAD,
+            self.address(),
+            self.power_mode,
+            onewire,
+            delay,
+        )?;
+        let hold = self.hold_pullup(master_pullup, pullup)?;
+        Ok((saved, hold))
+    }
+
+    /// Turns off the strong pull-up after a copy started with `begin_eeprom_copy`, then recalls the
+    /// EEPROM and checks that it holds `saved`
+    pub(crate) fn end_eeprom_copy<B, E>(
+        &self,
+        saved: &Scratchpad,
+        hold: PullupHold,
+        onewire: &mut B,
+        pullup: &mut impl StrongPullup,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        hold.release(onewire, pullup, delay)?;
+        self.retry_policy
+            .resume(&self.last_retries, delay, |delay| {
+                recall_from_eeprom(self.address(), onewire, delay)?;
+                verify_eeprom(saved, self.address(), onewire, delay)
+            })
+    }
+
+    pub(crate) fn recall_from_eeprom<B, E>(
+        &self,
+        onewire: &mut B,
+        delay: &mut impl DelayNs,
+    ) -> Ds18b20Result<(), E>
+    where
+        B: OneWireMaster<Error = E>,
+    {
+        self.retry(delay, |delay| {
+            recall_from_eeprom(self.address(), onewire, delay)
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use crate::Resolution;
+use core::fmt;
+use core::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// A temperature stored exactly as the sensor reports it, in 1/16 °C steps.
+///
+/// All accessors use integer math only, so no floating point code is pulled in
+/// unless [`Temperature::as_celsius_f32`] is used. The arithmetic operators saturate at the limits of
+/// the `i16` range, use [`Temperature::checked_add`] and [`Temperature::checked_sub`] to detect that.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Temperature(i16);
+
+impl Temperature {
+    pub const ZERO: Temperature = Temperature(0);
+
+    /// Creates a temperature from a count of 1/16 °C steps
+    pub const fn from_sixteenths(sixteenths: i16) -> Temperature {
+        Temperature(sixteenths)
+    }
+
+    /// Creates a temperature from whole degrees Celsius
+    pub const fn from_degrees(degrees: i8) -> Temperature {
+        Temperature(degrees as i16 * 16)
+    }
+
+    /// Decodes the two's complement temperature register. At lower resolutions the least
+    /// significant bits are undefined, so they are cleared.
+    pub(crate) fn from_register(raw_temp: i16, resolution: Resolution) -> Temperature {
+        let undefined_bits = match resolution {
+            Resolution::Bits12 => 0b000,
+            Resolution::Bits11 => 0b001,
+            Resolution::Bits10 => 0b011,
+            Resolution::Bits9 => 0b111,
+        };
+        Temperature(raw_temp & !undefined_bits)
+    }
+
+    /// The raw count of 1/16 °C steps
+    pub const fn as_sixteenths(self) -> i16 {
+        self.0
+    }
+
+    /// Whole degrees Celsius, with the fractional part truncated towards zero
+    pub const fn whole_degrees(self) -> i16 {
+        self.0 / 16
+    }
+
+    /// Thousandths of a degree Celsius, truncated towards zero
+    pub const fn millidegrees_celsius(self) -> i32 {
+        self.0 as i32 * 1000 / 16
+    }
+
+    /// Degrees Celsius as a float. This is exact, since every value fits in an `f32`
+    pub fn as_celsius_f32(self) -> f32 {
+        f32::from(self.0) / 16.0
+    }
+
+    /// Returns `None` if the sum is out of range
+    pub const fn checked_add(self, rhs: Temperature) -> Option<Temperature> {
+        match self.0.checked_add(rhs.0) {
+            Some(sixteenths) => Some(Temperature(sixteenths)),
+            None => None,
+        }
+    }
+
+    /// Returns `None` if the difference is out of range
+    pub const fn checked_sub(self, rhs: Temperature) -> Option<Temperature> {
+        match self.0.checked_sub(rhs.0) {
+            Some(sixteenths) => Some(Temperature(sixteenths)),
+            None => None,
+        }
+    }
+}
+
+impl From<Temperature> for f32 {
+    fn from(temperature: Temperature) -> f32 {
+        temperature.as_celsius_f32()
+    }
+}
+
+impl Add for Temperature {
+    type Output = Temperature;
+
+    fn add(self, rhs: Temperature) -> Temperature {
+        Temperature(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Temperature {
+    type Output = Temperature;
+
+    fn sub(self, rhs: Temperature) -> Temperature {
+        Temperature(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Neg for Temperature {
+    type Output = Temperature;
+
+    fn neg(self) -> Temperature {
+        Temperature(self.0.saturating_neg())
+    }
+}
+
+impl AddAssign for Temperature {
+    fn add_assign(&mut self, rhs: Temperature) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Temperature {
+    fn sub_assign(&mut self, rhs: Temperature) {
+        *self = *self - rhs;
+    }
+}
+
+/// Formats as degrees Celsius with as many decimal places as needed (at least one, at most four),
+/// without using floating point.
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.unsigned_abs();
+        // each 1/16 step is exactly 0.0625
+        let mut fraction = u32::from(magnitude % 16) * 625;
+        let mut digits = 4;
+        while digits > 1 && fraction % 10 == 0 {
+            fraction /= 10;
+            digits -= 1;
+        }
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            magnitude / 16,
+            fraction,
+            width = digits
+        )
+    }
+}
+
+impl fmt::Debug for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}°C", self)
+    }
+}
+
+/// The temperature register of a device, which only holds a measurement once a conversion has finished
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reading {
+    Temperature(Temperature),
+
+    /// The register still holds its power-on value (85 °C), so no conversion finished since the device
+    /// was powered up, e.g. after a brown-out or when a conversion was never started
+    PowerOnReset,
+}
+
+impl Reading {
+    /// Returns the measured temperature, or `None` after a power-on reset
+    pub fn temperature(self) -> Option<Temperature> {
+        match self {
+            Reading::Temperature(temperature) => Some(temperature),
+            Reading::PowerOnReset => None,
+        }
+    }
+
+    pub fn is_power_on_reset(self) -> bool {
+        self == Reading::PowerOnReset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::format;
+
+    #[test]
+    fn displays_without_floats() {
+        let cases = [
+            (0x07D0, "125.0"),
+            (0x0191, "25.0625"),
+            (0x00A2, "10.125"),
+            (0x0008, "0.5"),
+            (0x0000, "0.0"),
+            (-0x0008, "-0.5"),
+            (-0x00A2, "-10.125"),
+            (-0x0370, "-55.0"),
+        ];
+        for &(sixteenths, expected) in cases.iter() {
+            assert_eq!(
+                format!("{}", Temperature::from_sixteenths(sixteenths)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn exact_accessors() {
+        let temperature = Temperature::from_sixteenths(-0x0191);
+        assert_eq!(temperature.as_sixteenths(), -401);
+        assert_eq!(temperature.whole_degrees(), -25);
+        assert_eq!(temperature.millidegrees_celsius(), -25062);
+        assert_eq!(temperature.as_celsius_f32(), -25.0625);
+    }
+
+    #[test]
+    fn differences_and_ordering() {
+        let low = Temperature::from_degrees(-10);
+        let high = Temperature::from_sixteenths(0x00A2);
+        assert!(low < high);
+        assert_eq!(high - low, Temperature::from_sixteenths(0x0142));
+        assert_eq!(low + (high - low), high);
+    }
+
+    #[test]
+    fn arithmetic_saturates() {
+        let high = Temperature::from_sixteenths(32764);
+        let low = Temperature::from_sixteenths(-4000);
+        assert_eq!(high - low, Temperature::from_sixteenths(i16::MAX));
+        assert_eq!(high.checked_sub(low), None);
+        assert_eq!(low - high, Temperature::from_sixteenths(i16::MIN));
+        assert_eq!(high + high, Temperature::from_sixteenths(i16::MAX));
+        assert_eq!(high.checked_add(high), None);
+        assert_eq!(
+            -Temperature::from_sixteenths(i16::MIN),
+            Temperature::from_sixteenths(i16::MAX)
+        );
+
+        let mut temperature = low;
+        temperature -= high;
+        assert_eq!(temperature, Temperature::from_sixteenths(i16::MIN));
+        temperature += low;
+        assert_eq!(temperature, Temperature::from_sixteenths(i16::MIN));
+        assert_eq!(
+            low.checked_sub(Temperature::from_degrees(1)),
+            Some(Temperature::from_sixteenths(-4016))
+        );
+    }
+}
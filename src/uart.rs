@@ -0,0 +1,235 @@
+//! A `OneWireMaster` that generates the 1-Wire timing with a UART, instead of toggling a pin.
+//!
+//! TX and RX are connected to the 1-Wire line (TX through an open-drain buffer or a diode), so every
+//! byte sent is echoed back as seen on the bus. A reset is a single byte at 9600 baud, and every data
+//! slot is a single byte at 115200 baud.
+//! https://www.analog.com/en/resources/technical-articles/using-a-uart-to-implement-a-1wire-bus-master.html
+//!
+//! Changing the baud rate is not part of `embedded-io`, so the UART also needs to implement `SetBaudRate`.
+
+use crate::bus::OneWireMaster;
+use embedded_hal::delay::DelayNs;
+use embedded_io::{ErrorType, Read, ReadExactError, Write};
+use one_wire_bus::{OneWireError, OneWireResult};
+
+pub const RESET_BAUD_RATE: u32 = 9600;
+pub const DATA_BAUD_RATE: u32 = 115_200;
+
+/// Sent at 9600 baud, the low bits form the reset pulse. Devices pull the upper bits low when present
+const RESET_PULSE: u8 = 0xF0;
+/// Sent at 115200 baud, only the start bit is low (a write 1 or read slot)
+const SLOT_1: u8 = 0xFF;
+/// Sent at 115200 baud, the line is held low for the whole byte (a write 0 slot)
+const SLOT_0: u8 = 0x00;
+
+/// Implemented for the UART, since changing the baud rate is HAL specific
+pub trait SetBaudRate: ErrorType {
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum UartError<E> {
+    Io(E),
+
+    /// The byte that was sent was not echoed back. TX and RX are probably not connected to the bus
+    NoEcho,
+
+    /// The bus stayed low for the whole reset. It is probably shorted to ground
+    ShortDetected,
+}
+
+pub struct UartOneWire<U> {
+    uart: U,
+}
+
+impl<U, E> UartOneWire<U>
+where
+    U: Read<Error = E> + Write<Error = E> + SetBaudRate,
+{
+    /// Switches the UART to the data slot baud rate
+    pub fn new(mut uart: U) -> OneWireResult<UartOneWire<U>, UartError<E>> {
+        uart.set_baud_rate(DATA_BAUD_RATE).map_err(io_error)?;
+        Ok(UartOneWire { uart })
+    }
+
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    /// Sends a byte, and returns the byte that was seen on the bus
+    fn exchange(&mut self, byte: u8) -> OneWireResult<u8, UartError<E>> {
+        self.uart.write_all(&[byte]).map_err(io_error)?;
+        self.uart.flush().map_err(io_error)?;
+        let mut echo = [0];
+        self.uart.read_exact(&mut echo).map_err(|err| match err {
+            ReadExactError::UnexpectedEof => OneWireError::PinError(UartError::NoEcho),
+            ReadExactError::Other(err) => io_error(err),
+        })?;
+        Ok(echo[0])
+    }
+}
+
+fn io_error<E>(err: E) -> OneWireError<UartError<E>> {
+    OneWireError::PinError(UartError::Io(err))
+}
+
+impl<U, E> OneWireMaster for UartOneWire<U>
+where
+    U: Read<Error = E> + Write<Error = E> + SetBaudRate,
+{
+    type Error = UartError<E>;
+
+    fn reset(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        self.uart.set_baud_rate(RESET_BAUD_RATE).map_err(io_error)?;
+        let echo = self.exchange(RESET_PULSE);
+        self.uart.set_baud_rate(DATA_BAUD_RATE).map_err(io_error)?;
+        match echo? {
+            0x00 => Err(OneWireError::PinError(UartError::ShortDetected)),
+            echo => Ok(echo != RESET_PULSE),
+        }
+    }
+
+    fn read_bit(&mut self, _delay: &mut impl DelayNs) -> OneWireResult<bool, Self::Error> {
+        // a device sending a 0 holds the line low past the start bit
+        Ok(self.exchange(SLOT_1)? == SLOT_1)
+    }
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        _delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Self::Error> {
+        self.exchange(if value { SLOT_1 } else { SLOT_0 })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    /// A UART wired to a 1-Wire bus, which echoes every byte as the bus saw it
+    struct EchoUart {
+        baud_rate: u32,
+        /// Whether RX is connected to the bus
+        connected: bool,
+        /// The bits that devices hold low during the next byte
+        pulled_low: u8,
+        echo: Option<u8>,
+        /// Each byte written, with the baud rate it was sent at
+        sent: Vec<(u8, u32)>,
+    }
+
+    impl EchoUart {
+        fn new() -> EchoUart {
+            EchoUart {
+                baud_rate: 0,
+                connected: true,
+                pulled_low: 0,
+                echo: None,
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl ErrorType for EchoUart {
+        type Error = Infallible;
+    }
+
+    impl Write for EchoUart {
+        fn write(&mut self, bytes: &[u8]) -> Result<usize, Infallible> {
+            let byte = bytes[0];
+            self.sent.push((byte, self.baud_rate));
+            if self.connected {
+                self.echo = Some(byte & !self.pulled_low);
+            }
+            self.pulled_low = 0;
+            Ok(1)
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    impl Read for EchoUart {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Infallible> {
+            match self.echo.take() {
+                Some(byte) => {
+                    buffer[0] = byte;
+                    Ok(1)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl SetBaudRate for EchoUart {
+        fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Infallible> {
+            self.baud_rate = baud_rate;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reset_detects_presence_and_shorts() {
+        let mut bus = UartOneWire::new(EchoUart::new()).unwrap();
+        assert!(!bus.reset(&mut NoDelay).unwrap());
+        assert_eq!(bus.uart.sent, [(RESET_PULSE, RESET_BAUD_RATE)]);
+        assert_eq!(bus.uart.baud_rate, DATA_BAUD_RATE);
+
+        // a presence pulse pulls some of the upper bits low
+        bus.uart.pulled_low = 0xC0;
+        assert!(bus.reset(&mut NoDelay).unwrap());
+
+        bus.uart.pulled_low = 0xFF;
+        assert!(matches!(
+            bus.reset(&mut NoDelay),
+            Err(OneWireError::PinError(UartError::ShortDetected))
+        ));
+        assert_eq!(bus.uart.baud_rate, DATA_BAUD_RATE);
+    }
+
+    #[test]
+    fn reads_and_writes_bits() {
+        let mut bus = UartOneWire::new(EchoUart::new()).unwrap();
+        assert!(bus.read_bit(&mut NoDelay).unwrap());
+
+        // a device sending a 0 holds the line low after the start bit
+        bus.uart.pulled_low = 0xFE;
+        assert!(!bus.read_bit(&mut NoDelay).unwrap());
+
+        bus.write_bit(false, &mut NoDelay).unwrap();
+        bus.write_bit(true, &mut NoDelay).unwrap();
+        assert_eq!(
+            bus.uart.sent[2..],
+            [(SLOT_0, DATA_BAUD_RATE), (SLOT_1, DATA_BAUD_RATE)]
+        );
+    }
+
+    #[test]
+    fn missing_echo_is_reported() {
+        let mut uart = EchoUart::new();
+        uart.connected = false;
+        let mut bus = UartOneWire::new(uart).unwrap();
+        assert!(matches!(
+            bus.reset(&mut NoDelay),
+            Err(OneWireError::PinError(UartError::NoEcho))
+        ));
+        // the data baud rate is restored even so
+        assert_eq!(bus.uart.baud_rate, DATA_BAUD_RATE);
+        assert!(matches!(
+            bus.read_bit(&mut NoDelay),
+            Err(OneWireError::PinError(UartError::NoEcho))
+        ));
+    }
+}
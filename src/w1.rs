@@ -0,0 +1,339 @@
+//! Access to DS18B20 sensors through the Linux kernel's `w1` subsystem and `w1_therm` driver, for when the
+//! kernel owns the bus (for example with the `w1-gpio` overlay on a Raspberry Pi).
+//!
+//! Each sensor is a directory under `/sys/bus/w1/devices`, named after its family code and serial number
+//! (`28-0316a2795bff`). Reading its `temperature` attribute makes the kernel run a conversion.
+//! https://docs.kernel.org/w1/slaves/w1_therm.html
+
+use crate::{Ds18b20, PowerMode, Reading, Resolution, SensorData, SensorFamily, Temperature};
+use one_wire_bus::crc::crc8;
+use one_wire_bus::Address;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+pub const DEFAULT_ROOT: &str = "/sys/bus/w1/devices";
+
+pub struct W1Bus {
+    root: PathBuf,
+}
+
+impl W1Bus {
+    /// Uses the default sysfs location
+    pub fn new() -> W1Bus {
+        W1Bus::with_root(DEFAULT_ROOT)
+    }
+
+    /// Uses another directory laid out like `/sys/bus/w1/devices`
+    pub fn with_root(root: impl Into<PathBuf>) -> W1Bus {
+        W1Bus { root: root.into() }
+    }
+
+    /// Returns every DS18B20, DS1822 and DS1825 the kernel has found, sorted by serial number
+    pub fn devices(&self) -> io::Result<Vec<Ds18b20>> {
+        let mut addresses = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let name = entry?.file_name();
+            if let Some(address) = name.to_str().and_then(parse_device_name) {
+                if let Some(family) = SensorFamily::from_family_code(address.family_code()) {
+                    addresses.push((address, family));
+                }
+            }
+        }
+        addresses.sort_by_key(|(address, _)| address.0 & 0x00FF_FFFF_FFFF_FFFF);
+        let devices = addresses
+            .into_iter()
+            .map(|(address, family)| Ds18b20::with_address(address, family))
+            .collect();
+        Ok(devices)
+    }
+
+    /// Runs a conversion (this blocks for the conversion time) and reads the result and the configuration.
+    /// The kernel only reports the temperature, so the power-on value cannot be detected here
+    pub fn read_data(&self, sensor: &Ds18b20) -> io::Result<SensorData> {
+        let millidegrees: i32 = parse(&self.read_attribute(sensor, "temperature")?)?;
+        let resolution = self.resolution(sensor)?;
+        let (alarm_temp_low, alarm_temp_high) = self.alarms(sensor)?;
+        Ok(SensorData {
+            reading: Reading::Temperature(Temperature::from_register(
+                millidegrees_to_sixteenths(millidegrees)?,
+                resolution,
+            )),
+            resolution,
+            alarm_temp_low,
+            alarm_temp_high,
+            location: None,
+        })
+    }
+
+    pub fn resolution(&self, sensor: &Ds18b20) -> io::Result<Resolution> {
+        match parse::<u8>(&self.read_attribute(sensor, "resolution")?)? {
+            9 => Ok(Resolution::Bits9),
+            10 => Ok(Resolution::Bits10),
+            11 => Ok(Resolution::Bits11),
+            12 => Ok(Resolution::Bits12),
+            _ => Err(invalid_data("unknown resolution")),
+        }
+    }
+
+    /// Returns the (low, high) alarm thresholds
+    pub fn alarms(&self, sensor: &Ds18b20) -> io::Result<(i8, i8)> {
+        let alarms = self.read_attribute(sensor, "alarms")?;
+        let mut values = alarms.split_whitespace();
+        match (values.next(), values.next()) {
+            (Some(low), Some(high)) => Ok((parse(low)?, parse(high)?)),
+            _ => Err(invalid_data("expected the low and high alarm thresholds")),
+        }
+    }
+
+    pub fn power_mode(&self, sensor: &Ds18b20) -> io::Result<PowerMode> {
+        match parse::<i32>(&self.read_attribute(sensor, "ext_power")?)? {
+            0 => Ok(PowerMode::Parasite),
+            1 => Ok(PowerMode::External),
+            _ => Err(invalid_data("the kernel could not read the power supply")),
+        }
+    }
+
+    /// Writes the alarm thresholds and resolution to the scratchpad
+    pub fn set_config(
+        &self,
+        sensor: &mut Ds18b20,
+        alarm_temp_low: i8,
+        alarm_temp_high: i8,
+        resolution: Resolution,
+    ) -> io::Result<()> {
+        let bits = match resolution {
+            Resolution::Bits9 => 9,
+            Resolution::Bits10 => 10,
+            Resolution::Bits11 => 11,
+            Resolution::Bits12 => 12,
+        };
+        self.write_attribute(sensor, "resolution", &bits.to_string())?;
+        self.write_attribute(
+            sensor,
+            "alarms",
+            &std::format!("{} {}", alarm_temp_low, alarm_temp_high),
+        )?;
+        sensor.set_resolution(resolution);
+        Ok(())
+    }
+
+    pub fn save_to_eeprom(&self, sensor: &Ds18b20) -> io::Result<()> {
+        self.eeprom_command(sensor, "save")
+    }
+
+    pub fn recall_from_eeprom(&self, sensor: &Ds18b20) -> io::Result<()> {
+        self.eeprom_command(sensor, "restore")
+    }
+
+    fn eeprom_command(&self, sensor: &Ds18b20, command: &str) -> io::Result<()> {
+        // renamed from `eeprom` to `eeprom_cmd` in later kernels
+        match self.write_attribute(sensor, "eeprom_cmd", command) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                self.write_attribute(sensor, "eeprom", command)
+            }
+            result => result,
+        }
+    }
+
+    fn attribute_path(&self, sensor: &Ds18b20, attribute: &str) -> io::Result<PathBuf> {
+        // the kernel names devices after their address
+        let address = sensor
+            .address()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "sensor has no address"))?;
+        Ok(self.root.join(device_name(address)).join(attribute))
+    }
+
+    fn read_attribute(&self, sensor: &Ds18b20, attribute: &str) -> io::Result<String> {
+        fs::read_to_string(self.attribute_path(sensor, attribute)?)
+    }
+
+    fn write_attribute(&self, sensor: &Ds18b20, attribute: &str, value: &str) -> io::Result<()> {
+        let path = self.attribute_path(sensor, attribute)?;
+        if !path.exists() {
+            return Err(io::Error::new(ErrorKind::NotFound, "attribute not found"));
+        }
+        fs::write(path, value)
+    }
+}
+
+impl Default for W1Bus {
+    fn default() -> W1Bus {
+        W1Bus::new()
+    }
+}
+
+/// The kernel names devices by family code and serial number (most significant byte first), without the crc
+fn parse_device_name(name: &str) -> Option<Address> {
+    let (family, serial) = name.split_at(name.find('-')?);
+    let family = u8::from_str_radix(family, 16).ok()?;
+    let serial = u64::from_str_radix(&serial[1..], 16).ok()?;
+    if serial >> 48 != 0 {
+        return None;
+    }
+    let mut bytes = (u64::from(family) | serial << 8).to_le_bytes();
+    bytes[7] = crc8(&bytes[..7]);
+    Some(Address(u64::from_le_bytes(bytes)))
+}
+
+fn device_name(address: &Address) -> String {
+    std::format!(
+        "{:02x}-{:012x}",
+        address.family_code(),
+        (address.0 >> 8) & 0xFFFF_FFFF_FFFF
+    )
+}
+
+/// The kernel reports whole thousandths of a degree, truncated from the 1/16 °C register value
+fn millidegrees_to_sixteenths(millidegrees: i32) -> io::Result<i16> {
+    let rounding = if millidegrees < 0 { -500 } else { 500 };
+    let sixteenths = (millidegrees * 16 + rounding) / 1000;
+    i16::try_from(sixteenths).map_err(|_| invalid_data("temperature out of range"))
+}
+
+fn parse<T: core::str::FromStr>(value: &str) -> io::Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("unexpected attribute value"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fake `/sys/bus/w1/devices` in a fresh temporary directory
+    struct FakeSysfs {
+        root: PathBuf,
+    }
+
+    impl FakeSysfs {
+        fn new() -> FakeSysfs {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let root = std::env::temp_dir().join(std::format!(
+                "ds18b20-w1-{}-{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(root.join("w1_bus_master1")).unwrap();
+            FakeSysfs { root }
+        }
+
+        fn add_device(&self, name: &str, attributes: &[(&str, &str)]) {
+            let dir = self.root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(dir.join(attribute), value).unwrap();
+            }
+        }
+
+        fn attribute(&self, name: &str, attribute: &str) -> String {
+            fs::read_to_string(self.root.join(name).join(attribute)).unwrap()
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    const SENSOR_ATTRIBUTES: [(&str, &str); 5] = [
+        ("temperature", "-10125\n"),
+        ("resolution", "12\n"),
+        ("alarms", "-10 25\n"),
+        ("ext_power", "1\n"),
+        ("eeprom_cmd", ""),
+    ];
+
+    #[test]
+    fn discovers_only_ds18b20_devices() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("28-0316a2795bff", &SENSOR_ATTRIBUTES);
+        sysfs.add_device("28-000005e2fdc3", &SENSOR_ATTRIBUTES);
+        sysfs.add_device("10-000802b4ba0f", &[]);
+
+        let devices = W1Bus::with_root(&sysfs.root).devices().unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(
+            device_name(devices[0].address().unwrap()),
+            "28-000005e2fdc3"
+        );
+        assert_eq!(
+            device_name(devices[1].address().unwrap()),
+            "28-0316a2795bff"
+        );
+        // the crc is recomputed, so the address is valid on the bus
+        assert_eq!(crc8(&devices[0].address().unwrap().0.to_le_bytes()), 0);
+    }
+
+    #[test]
+    fn reads_sensor_data() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("28-0316a2795bff", &SENSOR_ATTRIBUTES);
+        let bus = W1Bus::with_root(&sysfs.root);
+        let sensor = &bus.devices().unwrap()[0];
+
+        let data = bus.read_data(sensor).unwrap();
+        assert_eq!(
+            data.reading,
+            Reading::Temperature(Temperature::from_sixteenths(-0x00A2))
+        );
+        assert_eq!(data.resolution, Resolution::Bits12);
+        assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (-10, 25));
+        assert_eq!(bus.power_mode(sensor).unwrap(), PowerMode::External);
+    }
+
+    #[test]
+    fn rounds_truncated_millidegrees() {
+        let cases = [
+            (25062, 0x0191),
+            (-25062, -0x0191),
+            (85000, 0x0550),
+            (-55000, -0x0370),
+        ];
+        for &(millidegrees, sixteenths) in cases.iter() {
+            assert_eq!(
+                millidegrees_to_sixteenths(millidegrees).unwrap(),
+                sixteenths
+            );
+        }
+    }
+
+    #[test]
+    fn writes_config_and_eeprom_commands() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("28-0316a2795bff", &SENSOR_ATTRIBUTES);
+        let bus = W1Bus::with_root(&sysfs.root);
+        let mut sensor = bus.devices().unwrap().remove(0);
+
+        bus.set_config(&mut sensor, -5, 30, Resolution::Bits10)
+            .unwrap();
+        assert_eq!(sysfs.attribute("28-0316a2795bff", "resolution"), "10");
+        assert_eq!(sysfs.attribute("28-0316a2795bff", "alarms"), "-5 30");
+        assert_eq!(sensor.resolution(), Resolution::Bits10);
+
+        bus.save_to_eeprom(&sensor).unwrap();
+        assert_eq!(sysfs.attribute("28-0316a2795bff", "eeprom_cmd"), "save");
+    }
+
+    #[test]
+    fn falls_back_to_the_old_eeprom_attribute() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("28-0316a2795bff", &[("eeprom", "")]);
+        let bus = W1Bus::with_root(&sysfs.root);
+        let sensor = &bus.devices().unwrap()[0];
+
+        bus.recall_from_eeprom(sensor).unwrap();
+        assert_eq!(sysfs.attribute("28-0316a2795bff", "eeprom"), "restore");
+    }
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "async")]
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use ds18b20::asynch::Ds18b20;
+use ds18b20::sim::{Fault, FaultyBus, SimBus, SimDevice};
+use ds18b20::{Address, NoStrongPullup, PowerMode, Resolution, RetryPolicy, Temperature};
+
+/// Runs a future to completion. The sim never leaves a future pending, so no waker is needed
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn bus_with_device(power_mode: PowerMode) -> (SimBus, Address) {
+    let mut bus = SimBus::new();
+    let mut device = SimDevice::new(0x0316a2795b);
+    device.set_power_mode(power_mode);
+    device.set_ambient(Temperature::from_degrees(30));
+    let address = device.address();
+    bus.add_device(device);
+    (bus, address)
+}
+
+#[test]
+fn measures_an_externally_powered_device() {
+    let (mut bus, address) = bus_with_device(PowerMode::External);
+    bus.device_mut(&address)
+        .unwrap()
+        .set_conversion_time_percent(50);
+    let mut delay = bus.delay();
+    let mut sensor = Ds18b20::new::<()>(address).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+
+    let started = bus.now_micros();
+    let data = block_on(sensor.measure(&mut bus, &mut NoStrongPullup, &mut delay)).unwrap();
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_degrees(30))
+    );
+    // done as soon as the device is, not after the maximum conversion time
+    assert!(bus.now_micros() - started < 500_000);
+}
+
+#[test]
+fn measures_a_parasite_powered_device() {
+    let (mut bus, address) = bus_with_device(PowerMode::Parasite);
+    let mut delay = bus.delay();
+    let mut pullup = bus.strong_pullup();
+    let sensor = Ds18b20::new::<()>(address).unwrap();
+
+    let data = block_on(sensor.measure(&mut bus, &mut pullup, &mut delay)).unwrap();
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_degrees(30))
+    );
+    assert!(!pullup.is_enabled());
+}
+
+#[test]
+fn saves_and_recalls_the_eeprom() {
+    let (mut bus, address) = bus_with_device(PowerMode::Parasite);
+    let mut delay = bus.delay();
+    let mut pullup = bus.strong_pullup();
+    let mut sensor = Ds18b20::new::<()>(address).unwrap();
+
+    block_on(sensor.set_config(-10, 25, Resolution::Bits10, &mut bus, &mut delay)).unwrap();
+    block_on(sensor.save_to_eeprom(&mut bus, &mut pullup, &mut delay)).unwrap();
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [25, 0xF6, 0x3F]);
+    assert!(!pullup.is_enabled());
+
+    block_on(sensor.set_config(0, 0, Resolution::Bits12, &mut bus, &mut delay)).unwrap();
+    block_on(sensor.recall_from_eeprom(&mut bus, &mut delay)).unwrap();
+    let data = block_on(sensor.read_data(&mut bus, &mut delay)).unwrap();
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (-10, 25));
+    assert_eq!(data.resolution, Resolution::Bits10);
+}
+
+#[test]
+fn retries_as_the_policy_says() {
+    let (bus, address) = bus_with_device(PowerMode::External);
+    let mut bus = FaultyBus::new(bus, 1);
+    let mut delay = bus.inner().delay();
+    let mut sensor = Ds18b20::new::<()>(address).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    bus.inject(Fault::Disconnect(address));
+    bus.schedule(2, Fault::Reconnect(address));
+    block_on(sensor.recall_from_eeprom(&mut bus, &mut delay)).unwrap();
+    assert_eq!(sensor.last_retries(), 2);
+
+    bus.inject(Fault::Disconnect(address));
+    bus.schedule(2, Fault::Reconnect(address));
+    block_on(sensor.measure(&mut bus, &mut NoStrongPullup, &mut delay)).unwrap();
+    assert_eq!(sensor.last_retries(), 2);
+}
@@ -0,0 +1,432 @@
+use ds18b20::sim::{Fault, FaultyBus, SimBus, SimDevice};
+use ds18b20::{
+    Address, Clock, Device, Ds18b20, Ds18b20Error, NoStrongPullup, OneWireError, PowerMode,
+    Reading, Resolution, RetryPolicy, StrongPullup, Temperature,
+};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital;
+
+/// Slots used to address a device: a reset, Match ROM, the address and a function command
+const COMMAND_SLOTS: u64 = 1 + 8 + 64 + 8;
+/// Read slots for the whole scratchpad
+const SCRATCHPAD_SLOTS: u64 = 9 * 8;
+
+fn faulty_bus(seed: u64) -> (FaultyBus, Ds18b20) {
+    let mut bus = SimBus::new();
+    let device = SimDevice::new(0x0316a2795b);
+    let address = device.address();
+    bus.add_device(device);
+    let mut sensor = Ds18b20::new::<()>(address).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+    (FaultyBus::new(bus, seed), sensor)
+}
+
+fn address(sensor: &Ds18b20) -> Address {
+    *sensor.address().unwrap()
+}
+
+#[test]
+fn flipped_bit_is_a_crc_mismatch() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+
+    bus.schedule(COMMAND_SLOTS + 3, Fault::FlipReadBit);
+    let result = sensor.read_data(&mut bus, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::CrcMismatch)));
+    assert_eq!(bus.flipped_bits(), 1);
+
+    // the device is fine, so reading again works
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+}
+
+#[test]
+fn random_bit_flips_are_deterministic() {
+    let run = |seed| {
+        let (mut bus, sensor) = faulty_bus(seed);
+        let mut delay = bus.inner().delay();
+        bus.set_bit_flips_per_million(2_000);
+        let results: Vec<bool> = (0..200)
+            .map(|_| sensor.read_data(&mut bus, &mut delay).is_ok())
+            .collect();
+        (results, bus.flipped_bits())
+    };
+    let (results, flipped_bits) = run(42);
+    assert_eq!(run(42), (results.clone(), flipped_bits));
+    assert!(flipped_bits > 0);
+    // every flipped bit in a scratchpad is caught by the crc
+    let failures = results.iter().filter(|ok| !**ok).count() as u32;
+    assert!(failures > 0 && failures <= flipped_bits);
+}
+
+#[test]
+fn vanishing_device_reads_all_ones() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+
+    bus.schedule(COMMAND_SLOTS, Fault::Disconnect(address(&sensor)));
+    let result = sensor.read_data(&mut bus, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::AllOnes)));
+
+    // gone before the reset
+    let scratchpad = ds18b20::read_scratchpad(sensor.address(), &mut bus, &mut delay);
+    assert!(matches!(scratchpad, Err(Ds18b20Error::NotPresent)));
+
+    bus.inject(Fault::Reconnect(address(&sensor)));
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+}
+
+#[test]
+fn device_vanishing_mid_read_is_a_crc_mismatch() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+
+    // half way through the scratchpad
+    bus.schedule(COMMAND_SLOTS + 36, Fault::Disconnect(address(&sensor)));
+    let result = sensor.read_data(&mut bus, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::CrcMismatch)));
+}
+
+#[test]
+fn vanishing_device_during_recall_goes_unnoticed() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+
+    // a missing device cannot hold the bus low, so it looks like the recall finished
+    bus.schedule(COMMAND_SLOTS, Fault::Disconnect(address(&sensor)));
+    assert!(sensor.recall_from_eeprom(&mut bus, &mut delay).is_ok());
+}
+
+#[test]
+fn stuck_low_bus() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    bus.inject(Fault::StuckLow);
+
+    assert!(matches!(
+        sensor.read_data(&mut bus, &mut delay),
+        Err(Ds18b20Error::Bus(OneWireError::BusNotHigh))
+    ));
+    assert!(matches!(
+        sensor.save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay),
+        Err(Ds18b20Error::Bus(OneWireError::BusNotHigh))
+    ));
+    assert!(matches!(
+        sensor.recall_from_eeprom(&mut bus, &mut delay),
+        Err(Ds18b20Error::Bus(OneWireError::BusNotHigh))
+    ));
+
+    bus.inject(Fault::Released);
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+}
+
+#[test]
+fn bus_shorted_during_recall_times_out() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    bus.schedule(COMMAND_SLOTS, Fault::StuckLow);
+    assert!(matches!(
+        sensor.recall_from_eeprom(&mut bus, &mut delay),
+        Err(Ds18b20Error::Timeout)
+    ));
+}
+
+#[test]
+fn slow_eeprom_write() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let address = address(&sensor);
+    bus.inner_mut()
+        .device_mut(&address)
+        .unwrap()
+        .set_eeprom_write_millis(25);
+
+    sensor
+        .set_config(-5, 40, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    // the driver only waits for the datasheet maximum, so the recall that checks it loads the old values
+    let result = sensor.save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::EepromWriteUnverified)));
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (70, 75));
+
+    // the write still finishes
+    delay.delay_ms(20);
+    let device = bus.inner_mut().device_mut(&address).unwrap();
+    assert_eq!(device.eeprom()[..2], [40, 0xFB]);
+}
+
+#[test]
+fn slow_eeprom_write_with_parasite_power() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let mut pullup = bus.inner().strong_pullup();
+    let address = address(&sensor);
+    let device = bus.inner_mut().device_mut(&address).unwrap();
+    device.set_power_mode(PowerMode::Parasite);
+    device.set_eeprom_write_millis(25);
+    sensor.set_power_mode(PowerMode::Parasite);
+
+    sensor
+        .set_config(-5, 40, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    // the strong pull-up is released too early, so the write never completes
+    let result = sensor.save_to_eeprom(&mut bus, &mut pullup, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::EepromWriteUnverified)));
+    delay.delay_ms(20);
+    let device = bus.inner_mut().device_mut(&address).unwrap();
+    assert_eq!(device.eeprom(), [75, 70, 0x7F]);
+}
+
+/// A pull-up pin with an error type of its own, unrelated to the bus master's
+struct BrokenPullup;
+
+#[derive(Debug)]
+struct PinFault;
+
+impl digital::Error for PinFault {
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
+
+impl StrongPullup for BrokenPullup {
+    type Error = PinFault;
+
+    fn enable(&mut self) -> Result<(), PinFault> {
+        Err(PinFault)
+    }
+
+    fn disable(&mut self) -> Result<(), PinFault> {
+        Ok(())
+    }
+}
+
+#[test]
+fn strong_pullup_errors_are_reported_separately() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    sensor.set_power_mode(PowerMode::Parasite);
+
+    let result = sensor.save_to_eeprom(&mut bus, &mut BrokenPullup, &mut delay);
+    assert!(matches!(
+        result,
+        Err(Ds18b20Error::StrongPullup(digital::ErrorKind::Other))
+    ));
+}
+
+#[test]
+fn brown_out_resets_to_power_on_values() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let clock = bus.inner().clock();
+    let address = address(&sensor);
+    bus.inner_mut()
+        .device_mut(&address)
+        .unwrap()
+        .set_ambient(Temperature::from_degrees(22));
+
+    sensor
+        .set_config(10, 30, Resolution::Bits9, &mut bus, &mut delay)
+        .unwrap();
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_degrees(22))
+    );
+
+    bus.inject(Fault::BrownOut(address));
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.reading, Reading::PowerOnReset);
+    // the unsaved configuration is lost
+    assert_eq!(data.resolution, Resolution::Bits12);
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (70, 75));
+}
+
+#[test]
+fn brown_out_during_conversion() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let clock = bus.inner().clock();
+    let address = address(&sensor);
+
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    bus.schedule(0, Fault::BrownOut(address));
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.reading, Reading::PowerOnReset);
+}
+
+#[test]
+fn retries_crc_mismatch() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    bus.schedule(COMMAND_SLOTS + 3, Fault::FlipReadBit);
+    let started = bus.inner().now_micros();
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+    assert_eq!(sensor.last_retries(), 1);
+    assert!(bus.inner().now_micros() - started >= 5_000);
+
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+    assert_eq!(sensor.last_retries(), 0);
+}
+
+#[test]
+fn retries_until_device_reconnects() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    bus.inject(Fault::Disconnect(address(&sensor)));
+    bus.schedule(2, Fault::Reconnect(address(&sensor)));
+    assert!(sensor.read_data(&mut bus, &mut delay).is_ok());
+    assert_eq!(sensor.last_retries(), 2);
+}
+
+#[test]
+fn polling_a_measurement_retries_the_read() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let clock = bus.inner().clock();
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    let measurement = sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    delay.delay_ms(750);
+    bus.schedule(COMMAND_SLOTS + 3, Fault::FlipReadBit);
+    let data = measurement
+        .poll(&mut bus, &mut NoStrongPullup, &mut delay, clock.now())
+        .unwrap();
+    assert_eq!(bus.flipped_bits(), 1);
+    assert!(data.reading.temperature().is_some());
+}
+
+#[test]
+fn starting_a_measurement_does_not_back_off() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let clock = bus.inner().clock();
+    sensor.set_retry_policy(RetryPolicy::new(3, 100));
+
+    bus.inject(Fault::Disconnect(address(&sensor)));
+    bus.schedule(2, Fault::Reconnect(address(&sensor)));
+    let started = bus.inner().now_micros();
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    assert_eq!(sensor.last_retries(), 2);
+    assert!(bus.inner().now_micros() - started < 10_000);
+}
+
+#[test]
+fn gives_up_after_all_attempts() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    bus.inject(Fault::Disconnect(address(&sensor)));
+    assert!(matches!(
+        sensor.read_data(&mut bus, &mut delay),
+        Err(Ds18b20Error::NotPresent)
+    ));
+    assert_eq!(sensor.last_retries(), 2);
+}
+
+#[test]
+fn does_not_retry_other_errors() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    sensor.set_retry_policy(RetryPolicy::new(3, 5));
+
+    // timeouts are only retried when asked for
+    bus.schedule(COMMAND_SLOTS, Fault::StuckLow);
+    assert!(matches!(
+        sensor.recall_from_eeprom(&mut bus, &mut delay),
+        Err(Ds18b20Error::Timeout)
+    ));
+    assert_eq!(sensor.last_retries(), 0);
+
+    assert!(matches!(
+        sensor.read_data(&mut bus, &mut delay),
+        Err(Ds18b20Error::Bus(OneWireError::BusNotHigh))
+    ));
+    assert_eq!(sensor.last_retries(), 0);
+}
+
+#[test]
+fn no_retries_by_default() {
+    let (mut bus, sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    assert_eq!(sensor.retry_policy(), RetryPolicy::NONE);
+
+    bus.schedule(COMMAND_SLOTS + 3, Fault::FlipReadBit);
+    assert!(matches!(
+        sensor.read_data(&mut bus, &mut delay),
+        Err(Ds18b20Error::CrcMismatch)
+    ));
+    assert_eq!(sensor.last_retries(), 0);
+}
+
+#[test]
+fn retries_do_not_hide_a_failed_eeprom_write() {
+    let (mut bus, mut sensor) = faulty_bus(1);
+    let mut delay = bus.inner().delay();
+    let address = address(&sensor);
+    let device = bus.inner_mut().device_mut(&address).unwrap();
+    device.set_power_mode(PowerMode::Parasite);
+    sensor.set_power_mode(PowerMode::Parasite);
+    sensor.set_retry_policy(RetryPolicy::new(3, 1));
+
+    sensor
+        .set_config(-5, 40, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    // without a strong pull-up the copy fails, and the recall loads the old values. The read that
+    // checks them is garbled, and has to be retried
+    let read = COMMAND_SLOTS + SCRATCHPAD_SLOTS;
+    let copy = COMMAND_SLOTS;
+    // the recall finishes at once, so a single read slot polls it
+    let recall = COMMAND_SLOTS + 1;
+    bus.schedule(read + copy + recall, Fault::FlipReadBit);
+    let result = sensor.save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::EepromWriteUnverified)));
+    assert_eq!(bus.flipped_bits(), 1);
+    assert_eq!(sensor.last_retries(), 1);
+    let device = bus.inner_mut().device_mut(&address).unwrap();
+    assert_eq!(device.eeprom(), [0x4B, 0x46, 0x7F]);
+}
+
+#[test]
+fn scan_goes_on_after_an_unreadable_ds1825() {
+    let mut bus = SimBus::new();
+    let sensor = SimDevice::new(1);
+    let ds1825 = SimDevice::with_family_code(0x3B, 1);
+    let (sensor_address, ds1825_address) = (sensor.address(), ds1825.address());
+    bus.add_device(sensor);
+    bus.add_device(ds1825);
+    let mut bus = FaultyBus::new(bus, 1);
+    let mut delay = bus.inner().delay();
+
+    // each search pass is a reset, the Search ROM command and 3 slots per address bit.
+    // The DS18B20 is found first, then the DS1825 scratchpad read gets a crc mismatch
+    let search_slots = 1 + 8 + 64 * 3;
+    bus.schedule(2 * search_slots + COMMAND_SLOTS + 3, Fault::FlipReadBit);
+    let mut devices: heapless::Vec<Device, 4> = heapless::Vec::new();
+    let found = ds18b20::scan(&mut bus, &mut delay, &mut devices).unwrap();
+    assert_eq!(bus.flipped_bits(), 1);
+    assert_eq!(found, 2);
+    assert!(matches!(&devices[0], Device::Ds18b20(_)));
+    assert_eq!(devices[0].address(), sensor_address);
+    assert!(matches!(&devices[1], Device::Unknown(address) if *address == ds1825_address));
+}
@@ -0,0 +1,865 @@
+use core::convert::Infallible;
+use ds18b20::sim::{SimBus, SimDevice};
+use ds18b20::{
+    Address, Clock, Device, Ds18b20, Ds18b20Error, NoStrongPullup, OneWireMaster, PowerMode,
+    Reading, Resolution, SensorFamily, Temperature, Vendor,
+};
+use embedded_hal::delay::DelayNs;
+use one_wire_bus::OneWireResult;
+
+fn bus_with_devices(serials: &[u64]) -> (SimBus, Vec<Address>) {
+    let mut bus = SimBus::new();
+    let mut addresses = Vec::new();
+    for &serial in serials {
+        let device = SimDevice::new(serial);
+        addresses.push(device.address());
+        bus.add_device(device);
+    }
+    (bus, addresses)
+}
+
+#[test]
+fn finds_every_device() {
+    let serials = [0x0316a2795b, 0x0316a2795c, 0x05e2fdc3, 0x0802b4ba0f, 0x01];
+    let (mut bus, mut addresses) = bus_with_devices(&serials);
+    let mut delay = bus.delay();
+
+    let mut found: Vec<Address> = OneWireMaster::devices(&mut bus, false, &mut delay)
+        .map(Result::unwrap)
+        .collect();
+    found.sort_by_key(|address| address.0);
+    addresses.sort_by_key(|address| address.0);
+    assert_eq!(found, addresses);
+}
+
+#[test]
+fn rejects_other_families() {
+    let result = Ds18b20::new::<()>(Address(0x10));
+    assert!(matches!(
+        result,
+        Err(Ds18b20Error::FamilyCodeMismatch(0x10))
+    ));
+}
+
+#[test]
+fn measures_the_ambient_temperature() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_ambient(Temperature::from_sixteenths(-0x0191));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+
+    // the power-on value, until a conversion is done
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.reading, Reading::PowerOnReset);
+
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    Resolution::Bits12.delay_for_measurement_time(&mut delay);
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_sixteenths(-0x0191))
+    );
+    assert_eq!(data.resolution, Resolution::Bits12);
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (70, 75));
+}
+
+#[test]
+fn reads_a_valid_scratchpad() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    let mut delay = bus.delay();
+    let scratchpad = ds18b20::read_scratchpad(Some(&addresses[0]), &mut bus, &mut delay).unwrap();
+    assert_eq!(scratchpad, bus.simulated_devices()[0].scratchpad());
+}
+
+#[test]
+fn polls_for_conversion_complete() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+
+    let started = bus.now_micros();
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let elapsed = bus.now_micros() - started;
+    // the simulated device takes 80% of the datasheet maximum
+    assert!((600_000..610_000).contains(&elapsed), "{}", elapsed);
+
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_conversion_time_percent(200);
+    ds18b20::start_simultaneous_temp_measurement(
+        &mut bus,
+        PowerMode::External,
+        Resolution::Bits12,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    let result = ds18b20::wait_for_conversion(&mut bus, &mut delay, 750);
+    assert!(matches!(result, Err(Ds18b20Error::Timeout)));
+}
+
+/// A bus master whose read slots take 1ms, like a bridge that needs several I²C transactions per slot
+struct SlowBus(SimBus);
+
+impl OneWireMaster for SlowBus {
+    type Error = Infallible;
+
+    fn reset(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        self.0.reset(delay)
+    }
+
+    fn read_bit(&mut self, delay: &mut impl DelayNs) -> OneWireResult<bool, Infallible> {
+        delay.delay_ms(1);
+        self.0.read_bit(delay)
+    }
+
+    fn write_bit(
+        &mut self,
+        value: bool,
+        delay: &mut impl DelayNs,
+    ) -> OneWireResult<(), Infallible> {
+        self.0.write_bit(value, delay)
+    }
+}
+
+#[test]
+fn conversion_timeout_does_not_depend_on_slot_time() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_conversion_time_percent(250);
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut bus = SlowBus(bus);
+
+    ds18b20::start_simultaneous_temp_measurement(
+        &mut bus,
+        PowerMode::External,
+        Resolution::Bits12,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    let started = clock.now();
+    let result = ds18b20::wait_for_conversion(&mut bus, &mut delay, 750);
+    assert!(matches!(result, Err(Ds18b20Error::Timeout)));
+    // the polls themselves add to the time, but not once per microsecond slot the timeout could hold
+    let elapsed = clock.now().millis_since(started);
+    assert!((750..2000).contains(&elapsed), "{}", elapsed);
+}
+
+#[test]
+fn non_blocking_measurement() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_ambient(Temperature::from_degrees(-55));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor
+        .set_config(-10, 30, Resolution::Bits9, &mut bus, &mut delay)
+        .unwrap();
+    sensor.set_power_mode(PowerMode::External);
+
+    let measurement = sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    assert_eq!(measurement.resolution(), Resolution::Bits9);
+    assert!(!measurement.is_ready(clock.now()));
+    assert!(matches!(
+        measurement.poll(&mut bus, &mut NoStrongPullup, &mut delay, clock.now()),
+        Err(nb::Error::WouldBlock)
+    ));
+
+    let mut polls = 0;
+    let data = loop {
+        match measurement.poll(&mut bus, &mut NoStrongPullup, &mut delay, clock.now()) {
+            Ok(data) => break data,
+            Err(nb::Error::WouldBlock) => delay.delay_ms(10),
+            Err(nb::Error::Other(err)) => panic!("{:?}", err),
+        }
+        polls += 1;
+    };
+    assert_eq!(polls, 10);
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_degrees(-55))
+    );
+}
+
+#[test]
+fn parasite_measurement_does_not_block() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    let device = bus.device_mut(&addresses[0]).unwrap();
+    device.set_power_mode(PowerMode::Parasite);
+    device.set_ambient(Temperature::from_degrees(30));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut pullup = bus.strong_pullup();
+    // parasite power is assumed
+    let sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+
+    let started = bus.now_micros();
+    let measurement = sensor
+        .start_temp_measurement(&mut bus, &clock, &mut pullup, &mut delay)
+        .unwrap();
+    // only the bus traffic for the command
+    assert!(bus.now_micros() - started < 10_000);
+    assert!(pullup.is_enabled());
+    assert!(matches!(
+        measurement.poll(&mut bus, &mut pullup, &mut delay, clock.now()),
+        Err(nb::Error::WouldBlock)
+    ));
+
+    delay.delay_ms(750);
+    let data = measurement
+        .poll(&mut bus, &mut pullup, &mut delay, clock.now())
+        .unwrap();
+    assert!(!pullup.is_enabled());
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_degrees(30))
+    );
+
+    // the blocking way turns the pull-up off too
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut pullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut pullup, &mut delay)
+        .unwrap();
+    assert!(!pullup.is_enabled());
+    assert!(!sensor
+        .read_data(&mut bus, &mut delay)
+        .unwrap()
+        .reading
+        .is_power_on_reset());
+}
+
+#[test]
+fn configures_and_uses_eeprom() {
+    let (mut bus, addresses) = bus_with_devices(&[1, 2]);
+    let mut delay = bus.delay();
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+
+    sensor
+        .set_config(-10, 25, Resolution::Bits10, &mut bus, &mut delay)
+        .unwrap();
+    assert_eq!(sensor.resolution(), Resolution::Bits10);
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (-10, 25));
+    assert_eq!(data.resolution, Resolution::Bits10);
+    // the EEPROM is unchanged until it is saved
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [75, 70, 0x7F]);
+
+    sensor
+        .save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [25, 0xF6, 0x3F]);
+    assert_eq!(bus.simulated_devices()[1].eeprom(), [75, 70, 0x7F]);
+
+    sensor
+        .set_config(0, 0, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    sensor.recall_from_eeprom(&mut bus, &mut delay).unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (-10, 25));
+}
+
+#[test]
+fn simultaneous_eeprom_operations() {
+    let (mut bus, addresses) = bus_with_devices(&[1, 2]);
+    let mut delay = bus.delay();
+    for address in addresses.iter() {
+        Ds18b20::new::<()>(*address)
+            .unwrap()
+            .set_config(5, 35, Resolution::Bits11, &mut bus, &mut delay)
+            .unwrap();
+    }
+    ds18b20::simultaneous_save_to_eeprom(
+        &mut bus,
+        PowerMode::External,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    for device in bus.simulated_devices() {
+        assert_eq!(device.eeprom(), [35, 5, 0x5F]);
+    }
+
+    for address in addresses.iter() {
+        Ds18b20::new::<()>(*address)
+            .unwrap()
+            .set_config(0, 0, Resolution::Bits9, &mut bus, &mut delay)
+            .unwrap();
+    }
+    ds18b20::simultaneous_recall_from_eeprom(&mut bus, &mut delay).unwrap();
+    for address in addresses.iter() {
+        let data = Ds18b20::new::<()>(*address)
+            .unwrap()
+            .read_data(&mut bus, &mut delay)
+            .unwrap();
+        assert_eq!(data.resolution, Resolution::Bits11);
+    }
+}
+
+#[test]
+fn detects_parasite_power() {
+    let (mut bus, addresses) = bus_with_devices(&[1, 2]);
+    let mut delay = bus.delay();
+    let mut sensors: Vec<Ds18b20> = addresses
+        .iter()
+        .map(|address| Ds18b20::new::<()>(*address).unwrap())
+        .collect();
+
+    assert!(!ds18b20::any_parasite_powered(&mut bus, &mut delay).unwrap());
+    bus.device_mut(&addresses[1])
+        .unwrap()
+        .set_power_mode(PowerMode::Parasite);
+    assert!(ds18b20::any_parasite_powered(&mut bus, &mut delay).unwrap());
+
+    assert!(!sensors[0]
+        .is_parasite_powered(&mut bus, &mut delay)
+        .unwrap());
+    assert!(sensors[1]
+        .is_parasite_powered(&mut bus, &mut delay)
+        .unwrap());
+    // assumed until detected
+    assert_eq!(sensors[0].power_mode(), PowerMode::Parasite);
+    assert_eq!(
+        sensors[0].detect_power_mode(&mut bus, &mut delay).unwrap(),
+        PowerMode::External
+    );
+    assert_eq!(sensors[0].power_mode(), PowerMode::External);
+    assert_eq!(
+        sensors[1].detect_power_mode(&mut bus, &mut delay).unwrap(),
+        PowerMode::Parasite
+    );
+}
+
+#[test]
+fn parasite_power_needs_the_strong_pullup() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    let device = bus.device_mut(&addresses[0]).unwrap();
+    device.set_power_mode(PowerMode::Parasite);
+    device.set_ambient(Temperature::from_degrees(21));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.detect_power_mode(&mut bus, &mut delay).unwrap();
+
+    // without a strong pull-up, the conversion fails and the power-on value remains
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.reading, Reading::PowerOnReset);
+
+    let mut pullup = bus.strong_pullup();
+    let measurement = sensor
+        .start_temp_measurement(&mut bus, &clock, &mut pullup, &mut delay)
+        .unwrap();
+    // the strong pull-up stays on until the conversion is waited for
+    assert!(pullup.is_enabled());
+    assert!(!measurement.is_ready(clock.now()));
+    sensor
+        .wait_for_conversion(&mut bus, &mut pullup, &mut delay)
+        .unwrap();
+    assert!(!pullup.is_enabled());
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_degrees(21))
+    );
+
+    sensor
+        .set_config(1, 2, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    let result = sensor.save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::EepromWriteUnverified)));
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [75, 70, 0x7F]);
+    // checking the write recalled the old values, so they need to be written again
+    sensor
+        .set_config(1, 2, Resolution::Bits12, &mut bus, &mut delay)
+        .unwrap();
+    sensor
+        .save_to_eeprom(&mut bus, &mut pullup, &mut delay)
+        .unwrap();
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [2, 1, 0x7F]);
+}
+
+#[test]
+fn finds_alarming_devices() {
+    let (mut bus, addresses) = bus_with_devices(&[1, 2, 3]);
+    let temperatures = [10, 30, -5];
+    for (address, &degrees) in addresses.iter().zip(temperatures.iter()) {
+        bus.device_mut(address)
+            .unwrap()
+            .set_ambient(Temperature::from_degrees(degrees));
+    }
+    let mut delay = bus.delay();
+    for address in addresses.iter() {
+        Ds18b20::new::<()>(*address)
+            .unwrap()
+            .set_config(0, 25, Resolution::Bits12, &mut bus, &mut delay)
+            .unwrap();
+    }
+    // no alarms before the first conversion
+    assert_eq!(ds18b20::alarming_devices(&mut bus, &mut delay).count(), 0);
+
+    ds18b20::start_simultaneous_temp_measurement(
+        &mut bus,
+        PowerMode::External,
+        Resolution::Bits12,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    Resolution::Bits12.delay_for_measurement_time(&mut delay);
+
+    let mut alarming: Vec<Address> = ds18b20::alarming_devices(&mut bus, &mut delay)
+        .map(|device| device.unwrap().address())
+        .collect();
+    alarming.sort_by_key(|address| address.0);
+    let mut expected = vec![addresses[1], addresses[2]];
+    expected.sort_by_key(|address| address.0);
+    assert_eq!(alarming, expected);
+}
+
+#[test]
+fn alarming_ds18s20_gets_its_own_handle() {
+    let mut bus = SimBus::new();
+    let device = SimDevice::with_family_code(0x10, 1);
+    let address = device.address();
+    bus.add_device(device);
+    bus.device_mut(&address)
+        .unwrap()
+        .set_ambient(Temperature::from_sixteenths(30 * 16 + 3));
+    let mut delay = bus.delay();
+    let sensor = ds18b20::ds18s20::Ds18s20::new::<()>(address).unwrap();
+    sensor.set_config(0, 25, &mut bus, &mut delay).unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (0, 25));
+    assert_eq!(data.reading, Reading::PowerOnReset);
+    ds18b20::start_simultaneous_temp_measurement(
+        &mut bus,
+        PowerMode::External,
+        Resolution::Bits12,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    Resolution::Bits12.delay_for_measurement_time(&mut delay);
+
+    let alarming: Vec<Device> = ds18b20::alarming_devices(&mut bus, &mut delay)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(alarming.len(), 1);
+    assert!(matches!(&alarming[0], Device::Ds18s20(sensor) if sensor.address() == Some(&address)));
+
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.half_degree_reading.temperature(),
+        Some(Temperature::from_degrees(30))
+    );
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_sixteenths(30 * 16 + 3))
+    );
+}
+
+#[test]
+fn alarm_search_without_alarms_is_empty() {
+    // nothing answers the reset
+    let mut bus = SimBus::new();
+    let mut delay = bus.delay();
+    assert!(ds18b20::alarming_devices(&mut bus, &mut delay)
+        .next()
+        .is_none());
+
+    // devices answer the reset, but none of them joins the search
+    let (mut bus, _) = bus_with_devices(&[1, 2]);
+    let mut delay = bus.delay();
+    assert!(ds18b20::alarming_devices(&mut bus, &mut delay)
+        .next()
+        .is_none());
+}
+
+#[test]
+fn missing_devices_do_not_respond() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    bus.device_mut(&addresses[0]).unwrap().set_connected(false);
+    let mut delay = bus.delay();
+    assert!(!bus.reset(&mut delay).unwrap());
+    assert_eq!(
+        OneWireMaster::devices(&mut bus, false, &mut delay).count(),
+        0
+    );
+}
+
+#[test]
+fn measured_85_degrees_is_not_a_power_on_reset() {
+    let (mut bus, addresses) = bus_with_devices(&[1]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_ambient(Temperature::from_degrees(85));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert!(data.reading.is_power_on_reset());
+
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_degrees(85))
+    );
+}
+
+#[test]
+fn single_device_without_its_address() {
+    let (mut bus, addresses) = bus_with_devices(&[0x0316a2795b]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_ambient(Temperature::from_sixteenths(0x00A2));
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+    let mut sensor = Ds18b20::single_on_bus();
+    assert_eq!(sensor.address(), None);
+
+    sensor.detect_power_mode(&mut bus, &mut delay).unwrap();
+    sensor
+        .set_config(-20, 60, Resolution::Bits11, &mut bus, &mut delay)
+        .unwrap();
+    sensor
+        .save_to_eeprom(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    sensor
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_sixteenths(0x00A2))
+    );
+    assert_eq!((data.alarm_temp_low, data.alarm_temp_high), (-20, 60));
+    assert_eq!(bus.simulated_devices()[0].eeprom(), [60, 0xEC, 0x5F]);
+
+    // Skip ROM is a single byte, Match ROM sends the whole address
+    let before = bus.stats().write_slots;
+    sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(bus.stats().write_slots - before, 8 + 8);
+
+    assert_eq!(
+        ds18b20::read_rom(&mut bus, &mut delay).unwrap(),
+        addresses[0]
+    );
+}
+
+#[test]
+fn read_rom_needs_a_single_device() {
+    let (mut bus, _) = bus_with_devices(&[]);
+    let mut delay = bus.delay();
+    let result = ds18b20::read_rom(&mut bus, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::NotPresent)));
+
+    let (mut bus, _) = bus_with_devices(&[0x0316a2795b, 0x05e2fdc3]);
+    let mut delay = bus.delay();
+    let result = ds18b20::read_rom(&mut bus, &mut delay);
+    assert!(matches!(result, Err(Ds18b20Error::CrcMismatch)));
+}
+
+#[test]
+fn ds1822_and_ds1825_are_accepted() {
+    let mut bus = SimBus::new();
+    let mut ds1822 = SimDevice::with_family_code(0x22, 1);
+    ds1822.set_ambient(Temperature::from_sixteenths(0x0191));
+    let mut ds1825 = SimDevice::with_family_code(0x3B, 2);
+    ds1825.set_location_pins(0b1010);
+    let addresses = [ds1822.address(), ds1825.address()];
+    bus.add_device(ds1822);
+    bus.add_device(ds1825);
+    let mut delay = bus.delay();
+    let clock = bus.clock();
+
+    ds18b20::start_simultaneous_temp_measurement(
+        &mut bus,
+        PowerMode::External,
+        Resolution::Bits12,
+        &mut NoStrongPullup,
+        &mut delay,
+    )
+    .unwrap();
+    delay.delay_ms(750);
+
+    let ds1822 = Ds18b20::new::<()>(addresses[0]).unwrap();
+    assert_eq!(ds1822.family(), SensorFamily::Ds1822);
+    let data = ds1822.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(
+        data.reading,
+        Reading::Temperature(Temperature::from_sixteenths(0x0191))
+    );
+    assert_eq!(data.location, None);
+
+    let mut ds1825 = Ds18b20::new::<()>(addresses[1]).unwrap();
+    assert_eq!(ds1825.family(), SensorFamily::Ds1825);
+    ds1825
+        .set_config(-10, 40, Resolution::Bits10, &mut bus, &mut delay)
+        .unwrap();
+    ds1825
+        .start_temp_measurement(&mut bus, &clock, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    ds1825
+        .wait_for_conversion(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    let data = ds1825.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.resolution, Resolution::Bits10);
+    assert_eq!(data.location, Some(0b1010));
+    assert_eq!(
+        data.reading.temperature(),
+        Some(Temperature::from_degrees(20))
+    );
+}
+
+#[test]
+fn scan_identifies_every_family() {
+    let mut bus = SimBus::new();
+    for &(family_code, serial) in [(0x28, 1), (0x10, 2), (0x22, 3), (0x3B, 4), (0x01, 5)].iter() {
+        bus.add_device(SimDevice::with_family_code(family_code, serial));
+    }
+    let mut delay = bus.delay();
+
+    let mut devices: heapless::Vec<Device, 8> = heapless::Vec::new();
+    let found = ds18b20::scan(&mut bus, &mut delay, &mut devices).unwrap();
+    assert_eq!(found, 5);
+    let mut families: Vec<(u8, &str)> = devices
+        .iter()
+        .map(|device| {
+            let kind = match device {
+                Device::Ds18b20(_) => "DS18B20",
+                Device::Ds18s20(_) => "DS18S20",
+                Device::Ds1822(_) => "DS1822",
+                Device::Ds1825(_) => "DS1825",
+                Device::Max31850(_) => "MAX31850",
+                Device::Unknown(_) => "unknown",
+            };
+            (device.address().family_code(), kind)
+        })
+        .collect();
+    families.sort();
+    assert_eq!(
+        families,
+        [
+            (0x01, "unknown"),
+            (0x10, "DS18S20"),
+            (0x22, "DS1822"),
+            (0x28, "DS18B20"),
+            (0x3B, "DS1825")
+        ]
+    );
+
+    let ds1825 = devices
+        .iter()
+        .find_map(|device| match device {
+            Device::Ds1825(sensor) => Some(sensor),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(ds1825.family(), SensorFamily::Ds1825);
+    assert!(ds1825.read_data(&mut bus, &mut delay).is_ok());
+}
+
+#[test]
+fn scan_tells_max31850_from_ds1825() {
+    let mut bus = SimBus::new();
+    let ds1825 = SimDevice::with_family_code(0x3B, 1);
+    let ds1825_address = ds1825.address();
+    bus.add_device(ds1825);
+    let mut converter = SimDevice::max31850(2);
+    let converter_address = converter.address();
+    converter.set_location_pins(0b0011);
+    converter.set_ambient(Temperature::from_degrees(25));
+    converter.set_thermocouple(Temperature::from_sixteenths(1612));
+    bus.add_device(converter);
+    let mut delay = bus.delay();
+
+    let mut devices: heapless::Vec<Device, 4> = heapless::Vec::new();
+    assert_eq!(
+        ds18b20::scan(&mut bus, &mut delay, &mut devices).unwrap(),
+        2
+    );
+    assert!(devices
+        .iter()
+        .any(|device| matches!(device, Device::Ds1825(_)) && device.address() == ds1825_address));
+    let mut converter = devices
+        .into_iter()
+        .find_map(|device| match device {
+            Device::Max31850(converter) => Some(converter),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(converter.address(), Some(&converter_address));
+
+    converter.set_power_mode(PowerMode::External);
+    converter
+        .start_temp_measurement(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    converter.wait_for_conversion(&mut bus, &mut delay).unwrap();
+    let data = converter.read_data(&mut bus, &mut delay).unwrap();
+    assert_eq!(data.temperature(), Ok(Temperature::from_sixteenths(1612)));
+    assert_eq!(data.cold_junction, Temperature::from_degrees(25));
+    assert_eq!(data.location, 0b0011);
+
+    // a DS18B20 handle for the converter reads it as the wrong kind of device
+    let sensor = Ds18b20::new::<()>(converter_address).unwrap();
+    assert!(matches!(
+        sensor.read_data(&mut bus, &mut delay),
+        Err(Ds18b20Error::Max31850)
+    ));
+}
+
+#[test]
+fn scan_stops_filling_when_full() {
+    let (mut bus, _) = bus_with_devices(&[1, 2, 3]);
+    let mut delay = bus.delay();
+
+    let mut devices: heapless::Vec<Device, 2> = heapless::Vec::new();
+    let found = ds18b20::scan(&mut bus, &mut delay, &mut devices).unwrap();
+    assert_eq!(found, 3);
+    assert_eq!(devices.len(), 2);
+}
+
+#[test]
+fn identifies_genuine_silicon() {
+    // genuine addresses end in 00-00 before the crc
+    let (mut bus, addresses) = bus_with_devices(&[0x16a2795b]);
+    let mut delay = bus.delay();
+
+    // still at the power-on value, so a conversion is run first
+    let mut sensor = Ds18b20::single_on_bus();
+    sensor.set_power_mode(PowerMode::External);
+    let vendor = sensor
+        .identify_silicon(&mut bus, &mut NoStrongPullup, &mut delay)
+        .unwrap();
+    assert_eq!(vendor, Vendor::Maxim);
+    assert!(vendor.is_genuine());
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert!(!data.reading.is_power_on_reset());
+
+    let sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    assert_eq!(
+        sensor
+            .identify_silicon(&mut bus, &mut NoStrongPullup, &mut delay)
+            .unwrap(),
+        Vendor::Maxim
+    );
+}
+
+#[test]
+fn identifies_parasite_powered_silicon() {
+    let (mut bus, addresses) = bus_with_devices(&[0x16a2795b]);
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_power_mode(PowerMode::Parasite);
+    let mut delay = bus.delay();
+    let mut pullup = bus.strong_pullup();
+
+    // the conversion only finishes with the strong pull-up held
+    let sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    assert_eq!(sensor.power_mode(), PowerMode::Parasite);
+    assert_eq!(
+        sensor
+            .identify_silicon(&mut bus, &mut pullup, &mut delay)
+            .unwrap(),
+        Vendor::Maxim
+    );
+    let data = sensor.read_data(&mut bus, &mut delay).unwrap();
+    assert!(!data.reading.is_power_on_reset());
+}
+
+#[test]
+fn clone_with_the_genuine_fingerprints_is_unknown() {
+    let (mut bus, addresses) = bus_with_devices(&[0x16a2795b]);
+    // copies the address pattern and byte 6, but not the undocumented commands
+    bus.device_mut(&addresses[0]).unwrap().set_trim(None);
+    let mut delay = bus.delay();
+
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+    assert_eq!(
+        sensor
+            .identify_silicon(&mut bus, &mut NoStrongPullup, &mut delay)
+            .unwrap(),
+        Vendor::Unknown
+    );
+}
+
+#[test]
+fn other_families_are_unknown_silicon() {
+    let mut bus = SimBus::new();
+    let device = SimDevice::with_family_code(0x22, 0x16a2795b);
+    let address = device.address();
+    bus.add_device(device);
+    bus.add_device(SimDevice::max31850(2));
+    let mut delay = bus.delay();
+
+    for address in [address, bus.simulated_devices()[1].address()] {
+        let sensor = Ds18b20::new::<()>(address).unwrap();
+        let started = bus.now_micros();
+        assert_eq!(
+            sensor
+                .identify_silicon(&mut bus, &mut NoStrongPullup, &mut delay)
+                .unwrap(),
+            Vendor::Unknown
+        );
+        // without touching the bus
+        assert_eq!(bus.now_micros(), started);
+    }
+}
+
+#[test]
+fn inconclusive_silicon_is_unknown() {
+    let (mut bus, addresses) = bus_with_devices(&[0x16a2795b]);
+    // 25.25 °C, where a fixed byte 6 of 0x0C matches by chance, on every conversion
+    bus.device_mut(&addresses[0])
+        .unwrap()
+        .set_ambient(Temperature::from_sixteenths(0x0194));
+    let mut delay = bus.delay();
+
+    let mut sensor = Ds18b20::new::<()>(addresses[0]).unwrap();
+    sensor.set_power_mode(PowerMode::External);
+    assert_eq!(
+        sensor
+            .identify_silicon(&mut bus, &mut NoStrongPullup, &mut delay)
+            .unwrap(),
+        Vendor::Unknown
+    );
+}